@@ -0,0 +1,41 @@
+use rust_fluid::fluid_solver::FluidSolver;
+use rust_fluid::{integration, linear_solvers, advection};
+use rust_fluid::boundary::{DomainBoundary, EdgeCondition, InflowProfile, SolidBody};
+
+extern crate image;
+
+// A NACA 2412 section at five degrees incidence in a wind tunnel, reporting its drag and lift coefficients. Theta
+// turns bodies anticlockwise in the images, so pitching the nose up into the flow takes a negative angle
+fn main() {
+    let width = 600;
+    let height = 300;
+    let speed = 1.0;
+    let chord = 0.5;
+
+    let mut buffer = vec![0u8; width * height * 3];
+
+    let bodies = vec![
+        SolidBody::new_airfoil(0.6, 0.5, "2412", chord, -5f64.to_radians(), 0.0, 0.0, 0.0)
+    ];
+
+    let mut solver = FluidSolver::new(height, width, 0.002, 1.0 / 300.0, 1.0, bodies)
+        .integration(integration::Integration::BogackiShampine)
+        .linear_solver(linear_solvers::LinearSolver::ConjugateGradient)
+        .advection(advection::Advection::SemiLagrangian)
+        .domain_boundary(DomainBoundary::new(EdgeCondition::Inflow(speed, InflowProfile::Uniform), EdgeCondition::Open(0.0), EdgeCondition::FreeSlip, EdgeCondition::FreeSlip))
+        .viscosity(1e-4);
+
+    for iteration in 0..300 {
+        for i in 0..4 {
+            print!("Step {}: ", 4 * iteration + i);
+            solver.add_inflow(0.0, 0.45, 0.02, 0.1, 1.0, speed, 0.0);
+            solver.update();
+        }
+
+        let (drag, lift, moment) = solver.force_coefficients(0, speed, chord);
+        println!("t = {:.3} s, Cd = {:.4}, Cl = {:.4}, Cm = {:.4}", solver.time(), drag, lift, moment);
+
+        solver.to_image(1.0, &mut buffer);
+        image::save_buffer(format!("output/{}.png", iteration), &buffer, width as u32, height as u32, image::RGB(8)).unwrap();
+    }
+}
@@ -0,0 +1,53 @@
+use rust_fluid::fluid_solver::FluidSolver;
+use rust_fluid::{integration, linear_solvers, advection};
+use rust_fluid::boundary::SolidBody;
+use rust_fluid::forces::BodyForce;
+use rust_fluid::interface::LevelSet;
+
+extern crate image;
+
+// A column of water collapses under gravity, hits a block on the floor and sloshes against the far wall
+fn main() {
+    let width = 400;
+    let height = 200;
+    let cell_size = 2.0 / width as f64;
+
+    let mut buffer = vec![0u8; width * height * 3];
+
+    let bodies = vec![
+        SolidBody::new_box(1.3, 0.925, 0.1, 0.15, 0.0, 0.0, 0.0, 0.0)
+    ];
+
+    let water = LevelSet::new(height, width, cell_size)
+        .add_region(&SolidBody::new_box(0.3, 0.7, 0.6, 0.6, 0.0, 0.0, 0.0, 0.0));
+
+    let mut solver = FluidSolver::new(height, width, 0.002, cell_size, 1000.0, bodies)
+        .integration(integration::Integration::BogackiShampine)
+        .linear_solver(linear_solvers::LinearSolver::ConjugateGradient)
+        .advection(advection::Advection::SemiLagrangian)
+        .add_body_force(BodyForce::Uniform(0.0, 9.81))
+        .liquid(water);
+
+    for iteration in 0..300 {
+        for i in 0..4 {
+            print!("Step {}: ", 4 * iteration + i);
+            solver.update();
+        }
+
+        let water = solver.level_set().unwrap();
+
+        for i in 0..(width * height) {
+            let colour = if solver.density.cell[i] == 1 {
+                [0, 0, 0]
+            } else if water.distance.src[i] < 0.0 {
+                [40, 90, 220]
+            } else {
+                [255, 255, 255]
+            };
+
+            buffer[i * 3..i * 3 + 3].copy_from_slice(&colour);
+        }
+
+        image::save_buffer(format!("output/{}.png", iteration), &buffer, width as u32, height as u32, image::RGB(8)).unwrap();
+    }
+}
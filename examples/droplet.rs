@@ -0,0 +1,41 @@
+use rust_fluid::fluid_solver::FluidSolver;
+use rust_fluid::{integration, linear_solvers, advection};
+use rust_fluid::boundary::SolidBody;
+use rust_fluid::interface::LevelSet;
+
+extern crate image;
+
+// A stretched water droplet a few millimetres across oscillates about its round shape under surface tension. Each
+// frame is split into many substeps by the capillary timestep limit
+fn main() {
+    let width = 200;
+    let height = 200;
+    let cell_size = 0.01 / width as f64;
+
+    let mut buffer = vec![0u8; width * height * 3];
+
+    let water = LevelSet::new(height, width, cell_size)
+        .add_region(&SolidBody::new_ellipse(0.005, 0.005, 0.0055, 0.0035, 0.0, 0.0, 0.0, 0.0));
+
+    let mut solver = FluidSolver::new(height, width, 0.0005, cell_size, 1000.0, Vec::new())
+        .integration(integration::Integration::BogackiShampine)
+        .linear_solver(linear_solvers::LinearSolver::ConjugateGradient)
+        .advection(advection::Advection::SemiLagrangian)
+        .viscosity(1e-6)
+        .liquid(water)
+        .surface_tension(0.072);
+
+    for iteration in 0..200 {
+        print!("Step {}: ", iteration);
+        solver.update();
+
+        let water = solver.level_set().unwrap();
+
+        for i in 0..(width * height) {
+            let colour = if water.distance.src[i] < 0.0 { [40, 90, 220] } else { [255, 255, 255] };
+            buffer[i * 3..i * 3 + 3].copy_from_slice(&colour);
+        }
+
+        image::save_buffer(format!("output/{}.png", iteration), &buffer, width as u32, height as u32, image::RGB(8)).unwrap();
+    }
+}
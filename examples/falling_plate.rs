@@ -0,0 +1,36 @@
+use rust_fluid::fluid_solver::FluidSolver;
+use rust_fluid::{integration, linear_solvers, advection};
+use rust_fluid::boundary::SolidBody;
+use rust_fluid::forces::BodyForce;
+
+extern crate image;
+
+// A tilted plate denser than the surrounding fluid falls under gravity, tumbling and fluttering as vortices shed
+fn main() {
+    let width = 300;
+    let height = 500;
+
+    let mut buffer = vec![0u8; width * height * 3];
+
+    let bodies = vec![
+        SolidBody::new_box(0.5, 0.2, 0.2, 0.03, 0.3, 0.0, 0.0, 0.0).solid_density(3.0)
+    ];
+
+    let mut solver = FluidSolver::new(height, width, 0.002, 1.0 / 300.0, 1.0, bodies)
+        .integration(integration::Integration::BogackiShampine)
+        .linear_solver(linear_solvers::LinearSolver::ConjugateGradient)
+        .advection(advection::Advection::SemiLagrangian)
+        .add_body_force(BodyForce::Uniform(0.0, 9.81))
+        .viscosity(1e-4);
+
+    for iteration in 0..300 {
+        for i in 0..4 {
+            print!("Step {}: ", 4 * iteration + i);
+            solver.add_inflow(0.45, 0.15, 0.1, 0.1, 1.0, 0.0, 0.0);
+            solver.update();
+        }
+
+        solver.to_image(1.0, &mut buffer);
+        image::save_buffer(format!("output/{}.png", iteration), &buffer, width as u32, height as u32, image::RGB(8)).unwrap();
+    }
+}
@@ -0,0 +1,41 @@
+use rust_fluid::fluid_solver::FluidSolver;
+use rust_fluid::{integration, linear_solvers, advection};
+use rust_fluid::boundary::{DomainBoundary, EdgeCondition, InflowProfile, SolidBody};
+
+extern crate image;
+
+// A NACA 0012 section heaving and pitching in a slow stream sheds a reverse von Karman street and produces thrust
+fn main() {
+    let width = 600;
+    let height = 300;
+    let speed = 0.5;
+    let chord = 0.25;
+
+    let mut buffer = vec![0u8; width * height * 3];
+
+    let bodies = vec![
+        SolidBody::new_airfoil(0.5, 0.5, "0012", chord, 0.0, 0.0, 0.0, 0.0)
+            .oscillate(0.1, 20f64.to_radians(), 1.0, std::f64::consts::FRAC_PI_2)
+    ];
+
+    let mut solver = FluidSolver::new(height, width, 0.002, 1.0 / 300.0, 1.0, bodies)
+        .integration(integration::Integration::BogackiShampine)
+        .linear_solver(linear_solvers::LinearSolver::ConjugateGradient)
+        .advection(advection::Advection::SemiLagrangian)
+        .domain_boundary(DomainBoundary::new(EdgeCondition::Inflow(speed, InflowProfile::Uniform), EdgeCondition::Open(0.0), EdgeCondition::FreeSlip, EdgeCondition::FreeSlip))
+        .viscosity(1e-4);
+
+    for iteration in 0..500 {
+        for i in 0..4 {
+            print!("Step {}: ", 4 * iteration + i);
+            solver.add_inflow(0.0, 0.4, 0.02, 0.2, 1.0, speed, 0.0);
+            solver.update();
+        }
+
+        let (drag, lift, _) = solver.force_coefficients(0, speed, chord);
+        println!("t = {:.3} s, Cd = {:.4}, Cl = {:.4}", solver.time(), drag, lift);
+
+        solver.to_image(1.0, &mut buffer);
+        image::save_buffer(format!("output/{}.png", iteration), &buffer, width as u32, height as u32, image::RGB(8)).unwrap();
+    }
+}
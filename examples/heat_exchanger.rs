@@ -0,0 +1,48 @@
+use rust_fluid::fluid_solver::FluidSolver;
+use rust_fluid::{integration, linear_solvers, advection};
+use rust_fluid::boundary::{DomainBoundary, EdgeCondition, InflowProfile, SolidBody, ThermalCondition};
+use rust_fluid::util::helper::clamp;
+
+extern crate image;
+
+// Cold air flows past a cylinder held hot, a cylinder releasing a fixed heat flux and a hot copper-like cylinder that
+// cools as it conducts heat to its surface. The Nusselt number of each cylinder is printed as the wakes develop
+fn main() {
+    let width = 600;
+    let height = 300;
+    let speed = 1.0;
+    let diameter = 0.1;
+
+    let mut buffer = vec![0u8; width * height * 3];
+
+    let bodies = vec![
+        SolidBody::new_sphere(0.4, 0.2, diameter, 0.0, 0.0, 0.0, 0.0).thermal_condition(ThermalCondition::FixedTemperature(1.0)),
+        SolidBody::new_sphere(0.4, 0.5, diameter, 0.0, 0.0, 0.0, 0.0).thermal_condition(ThermalCondition::HeatFlux(0.05)),
+        SolidBody::new_sphere(0.4, 0.8, diameter, 0.0, 0.0, 0.0, 0.0).thermal_condition(ThermalCondition::Conducting(0.05, 50.0, 1.0))
+    ];
+
+    // Reynolds number 100 and Prandtl number 0.7 based on the cylinder diameter
+    let mut solver = FluidSolver::new(height, width, 0.002, 1.0 / 300.0, 1.0, bodies)
+        .integration(integration::Integration::BogackiShampine)
+        .linear_solver(linear_solvers::LinearSolver::ConjugateGradient)
+        .advection(advection::Advection::SemiLagrangian)
+        .domain_boundary(DomainBoundary::new(EdgeCondition::Inflow(speed, InflowProfile::Uniform), EdgeCondition::Open(0.0), EdgeCondition::FreeSlip, EdgeCondition::FreeSlip))
+        .viscosity(speed * diameter / 100.0)
+        .thermal_diffusivity(speed * diameter / 70.0);
+
+    for iteration in 0..300 {
+        for i in 0..4 {
+            print!("Step {}: ", 4 * iteration + i);
+            solver.update();
+        }
+
+        println!("Nusselt: {:.3} {:.3} {:.3}", solver.nusselt_number(0, 0.0, diameter), solver.nusselt_number(1, 0.0, diameter), solver.nusselt_number(2, 0.0, diameter));
+
+        for (i, &temperature) in solver.temperature.src.iter().enumerate() {
+            let shade = (clamp(temperature, 0.0, 1.0) * 255.0) as u8;
+            buffer[i * 3..i * 3 + 3].copy_from_slice(&[shade, shade / 4, 255 - shade]);
+        }
+
+        image::save_buffer(format!("output/{}.png", iteration), &buffer, width as u32, height as u32, image::RGB(8)).unwrap();
+    }
+}
@@ -0,0 +1,41 @@
+use rust_fluid::fluid_solver::FluidSolver;
+use rust_fluid::{integration, linear_solvers, advection};
+use rust_fluid::boundary::SolidBody;
+
+extern crate image;
+
+// Three coloured streams meeting behind a cylinder, each carried by its own scalar field
+fn main() {
+    let width = 600;
+    let height = 300;
+
+    let mut buffer = vec![0u8; width * height * 3];
+
+    let bodies = vec![
+        SolidBody::new_sphere(0.8, 0.5, 0.15, 0.0, 0.0, 0.0, 0.0)
+    ];
+
+    let mut solver = FluidSolver::new(height, width, 0.005, 1.0 / 300.0, 0.1, bodies)
+        .integration(integration::Integration::BogackiShampine)
+        .linear_solver(linear_solvers::LinearSolver::ConjugateGradient)
+        .advection(advection::Advection::SemiLagrangian)
+        .add_scalar("red")
+        .add_scalar("green")
+        .add_scalar("blue");
+
+    for iteration in 0..300 {
+        for i in 0..4 {
+            print!("Step {}: ", 4 * iteration + i);
+            solver.add_inflow(0.05, 0.1, 0.15, 0.2, 0.0, 2.0, 0.5);
+            solver.add_inflow(0.05, 0.4, 0.15, 0.2, 0.0, 2.0, 0.0);
+            solver.add_inflow(0.05, 0.7, 0.15, 0.2, 0.0, 2.0, -0.5);
+            solver.add_scalar_inflow("red", 0.05, 0.1, 0.15, 0.2, 1.0);
+            solver.add_scalar_inflow("green", 0.05, 0.4, 0.15, 0.2, 1.0);
+            solver.add_scalar_inflow("blue", 0.05, 0.7, 0.15, 0.2, 1.0);
+            solver.update();
+        }
+
+        solver.to_image_rgb(["red", "green", "blue"], 1.0, &mut buffer);
+        image::save_buffer(format!("output/{}.png", iteration), &buffer, width as u32, height as u32, image::RGB(8)).unwrap();
+    }
+}
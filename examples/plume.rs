@@ -0,0 +1,35 @@
+use rust_fluid::fluid_solver::FluidSolver;
+use rust_fluid::{integration, linear_solvers, advection};
+use rust_fluid::boundary::SolidBody;
+
+extern crate image;
+
+// Hot smoke released near the floor rises under buoyancy and splits around a plate
+fn main() {
+    let width = 300;
+    let height = 500;
+
+    let mut buffer = vec![0u8; width * height * 3];
+
+    let bodies = vec![
+        SolidBody::new_box(0.5, 0.6, 0.3, 0.05, 0.0, 0.0, 0.0, 0.0)
+    ];
+
+    let mut solver = FluidSolver::new(height, width, 0.005, 1.0 / 300.0, 0.1, bodies)
+        .integration(integration::Integration::BogackiShampine)
+        .linear_solver(linear_solvers::LinearSolver::ConjugateGradient)
+        .advection(advection::Advection::SemiLagrangian)
+        .buoyancy(9.81, 0.05, 0.2, 0.0);
+
+    for iteration in 0..300 {
+        for i in 0..4 {
+            print!("Step {}: ", 4 * iteration + i);
+            solver.add_inflow(0.4, 1.45, 0.2, 0.1, 1.0, 0.0, 0.0);
+            solver.add_temperature_inflow(0.4, 1.45, 0.2, 0.1, 5.0);
+            solver.update();
+        }
+
+        solver.to_image(1.0, &mut buffer);
+        image::save_buffer(format!("output/{}.png", iteration), &buffer, width as u32, height as u32, image::RGB(8)).unwrap();
+    }
+}
@@ -0,0 +1,41 @@
+use rust_fluid::fluid_solver::FluidSolver;
+use rust_fluid::{integration, linear_solvers, advection};
+use rust_fluid::boundary::{DomainBoundary, EdgeCondition, InflowProfile, PorousRegion, SolidBody};
+
+extern crate image;
+
+// Smoke is blown at a solid block and a porous block of the same size. Flow bleeds through the porous block and
+// leaves a weaker, shorter wake than the solid one
+fn main() {
+    let width = 600;
+    let height = 300;
+    let speed = 1.0;
+
+    let mut buffer = vec![0u8; width * height * 3];
+
+    let bodies = vec![
+        SolidBody::new_box(0.4, 0.25, 0.1, 0.2, 0.0, 0.0, 0.0, 0.0)
+    ];
+
+    let screen = PorousRegion::new(SolidBody::new_box(0.4, 0.75, 0.1, 0.2, 0.0, 0.0, 0.0, 0.0), 2e-5);
+
+    let mut solver = FluidSolver::new(height, width, 0.002, 1.0 / 300.0, 1.0, bodies)
+        .integration(integration::Integration::BogackiShampine)
+        .linear_solver(linear_solvers::LinearSolver::ConjugateGradient)
+        .advection(advection::Advection::SemiLagrangian)
+        .domain_boundary(DomainBoundary::new(EdgeCondition::Inflow(speed, InflowProfile::Uniform), EdgeCondition::Open(0.0), EdgeCondition::FreeSlip, EdgeCondition::FreeSlip))
+        .viscosity(1e-4)
+        .add_porous_region(screen);
+
+    for iteration in 0..300 {
+        for i in 0..4 {
+            print!("Step {}: ", 4 * iteration + i);
+            solver.add_inflow(0.0, 0.15, 0.02, 0.2, 1.0, speed, 0.0);
+            solver.add_inflow(0.0, 0.65, 0.02, 0.2, 1.0, speed, 0.0);
+            solver.update();
+        }
+
+        solver.to_image(1.0, &mut buffer);
+        image::save_buffer(format!("output/{}.png", iteration), &buffer, width as u32, height as u32, image::RGB(8)).unwrap();
+    }
+}
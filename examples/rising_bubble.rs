@@ -0,0 +1,45 @@
+use rust_fluid::fluid_solver::FluidSolver;
+use rust_fluid::{integration, linear_solvers, advection};
+use rust_fluid::boundary::SolidBody;
+use rust_fluid::forces::BodyForce;
+use rust_fluid::interface::VolumeFraction;
+
+extern crate image;
+
+// An air bubble a centimetre across rises through a column of water, flattening as it goes. The bubble is the fluid
+// tracked by volume fractions, so in the closed box its area is conserved to machine precision however far it rises
+fn main() {
+    let width = 80;
+    let height = 160;
+    let cell_size = 0.04 / width as f64;
+
+    let mut buffer = vec![0u8; width * height * 3];
+
+    let air = VolumeFraction::new(height, width, cell_size)
+        .add_region(&SolidBody::new_sphere(0.02, 0.065, 0.01, 0.0, 0.0, 0.0, 0.0));
+
+    let mut solver = FluidSolver::new(height, width, 0.002, cell_size, 1.2, Vec::new())
+        .integration(integration::Integration::BogackiShampine)
+        .linear_solver(linear_solvers::LinearSolver::ConjugateGradient)
+        .advection(advection::Advection::SemiLagrangian)
+        .viscosity(1e-5)
+        .add_body_force(BodyForce::Uniform(0.0, 9.81))
+        .two_phase_fraction(air, 1000.0)
+        .surface_tension(0.072);
+
+    for iteration in 0..200 {
+        print!("Step {}: ", iteration);
+        solver.update();
+
+        let air = solver.volume_fraction().unwrap();
+        println!("Bubble area: {:.6e}", air.volume());
+
+        for i in 0..(width * height) {
+            let water = 1.0 - air.fraction.src[i];
+            let colour = [(255.0 - 215.0 * water) as u8, (255.0 - 165.0 * water) as u8, (255.0 - 35.0 * water) as u8];
+            buffer[i * 3..i * 3 + 3].copy_from_slice(&colour);
+        }
+
+        image::save_buffer(format!("output/{}.png", iteration), &buffer, width as u32, height as u32, image::RGB(8)).unwrap();
+    }
+}
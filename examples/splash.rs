@@ -0,0 +1,44 @@
+use rust_fluid::fluid_solver::FluidSolver;
+use rust_fluid::{integration, linear_solvers, advection};
+use rust_fluid::boundary::SolidBody;
+use rust_fluid::forces::BodyForce;
+use rust_fluid::interface::LevelSet;
+
+extern crate image;
+
+// A drop of water falls through air into a pool. Unlike a free surface the air is simulated too, a thousand times
+// lighter than the water, so it is pushed aside by the drop and trapped in pockets by the splash
+fn main() {
+    let width = 200;
+    let height = 200;
+    let cell_size = 0.1 / width as f64;
+
+    let mut buffer = vec![0u8; width * height * 3];
+
+    let water = LevelSet::new(height, width, cell_size)
+        .add_region(&SolidBody::new_box(0.05, 0.085, 0.1, 0.03, 0.0, 0.0, 0.0, 0.0))
+        .add_region(&SolidBody::new_sphere(0.05, 0.03, 0.015, 0.0, 0.0, 0.0, 0.0));
+
+    let mut solver = FluidSolver::new(height, width, 0.001, cell_size, 1000.0, Vec::new())
+        .integration(integration::Integration::BogackiShampine)
+        .linear_solver(linear_solvers::LinearSolver::ConjugateGradient)
+        .advection(advection::Advection::SemiLagrangian)
+        .viscosity(1e-6)
+        .add_body_force(BodyForce::Uniform(0.0, 9.81))
+        .two_phase(water, 1.2);
+
+    for iteration in 0..300 {
+        print!("Step {}: ", iteration);
+        solver.update();
+
+        let densities = solver.cell_density();
+
+        for i in 0..(width * height) {
+            let fraction = densities[i] / 1000.0;
+            let colour = [(255.0 - 215.0 * fraction) as u8, (255.0 - 165.0 * fraction) as u8, (255.0 - 35.0 * fraction) as u8];
+            buffer[i * 3..i * 3 + 3].copy_from_slice(&colour);
+        }
+
+        image::save_buffer(format!("output/{}.png", iteration), &buffer, width as u32, height as u32, image::RGB(8)).unwrap();
+    }
+}
@@ -6,8 +6,17 @@ pub enum Advection {
     SemiLagrangian
 }
 
+// Traces a grid point backwards through the velocity field to find where it came from
+fn trace(x: f64, y: f64, u_velocity: &FluidQuantity, v_velocity: &FluidQuantity, timestep: f64, interpolation: &Interpolation, integration: &Integration) -> (f64, f64) {
+    let f1 = |_t: f64, y0: f64| -interpolation.run(y0, y, u_velocity) / timestep;
+    let f2 = |_t: f64, y0: f64| -interpolation.run(x, y0, v_velocity) / timestep;
+
+    (integration.run(0.0, x, &f1, timestep), integration.run(0.0, y, &f2, timestep))
+}
+
 impl Advection {
-    pub fn advect(&self, u_velocity: &mut FluidQuantity, v_velocity: &mut FluidQuantity, density: &mut FluidQuantity, timestep: f64, interpolation: &Interpolation, integration: &Integration) {
+    // Advects the velocity field and every scalar through the velocity field from the start of the step
+    pub fn advect(&self, u_velocity: &mut FluidQuantity, v_velocity: &mut FluidQuantity, scalars: &mut [&mut FluidQuantity], timestep: f64, interpolation: &Interpolation, integration: &Integration) {
         match self {
             Advection::SemiLagrangian => {
                 for row in 0..u_velocity.rows {
@@ -15,11 +24,7 @@ impl Advection {
                         let x = column as f64 + u_velocity.x_offset;
                         let y = row as f64 + u_velocity.y_offset;
 
-                        let f1 = |_t: f64, y0: f64| -1.0 * interpolation.run(y0, y, &u_velocity) / timestep;
-                        let f2 = |_t: f64, y0: f64| -1.0 * interpolation.run(x, y0, &v_velocity) / timestep;
-
-                        let old_x: f64 = integration.run(0.0, x, &f1, timestep);
-                        let old_y: f64 = integration.run(0.0, y, &f2, timestep);
+                        let (old_x, old_y) = trace(x, y, u_velocity, v_velocity, timestep, interpolation, integration);
 
                         *u_velocity.dst_at_mut(row, column) = interpolation.run(old_x, old_y, u_velocity);
                     }
@@ -30,35 +35,32 @@ impl Advection {
                         let x = column as f64 + v_velocity.x_offset;
                         let y = row as f64 + v_velocity.y_offset;
 
-                        let f1 = |_t: f64, y0: f64| -1.0 * interpolation.run(y0, y, &u_velocity) / timestep;
-                        let f2 = |_t: f64, y0: f64| -1.0 * interpolation.run(x, y0, &v_velocity) / timestep;
-
-                        let old_x: f64 = integration.run(0.0, x, &f1, timestep);
-                        let old_y: f64 = integration.run(0.0, y, &f2, timestep);
+                        let (old_x, old_y) = trace(x, y, u_velocity, v_velocity, timestep, interpolation, integration);
 
                         *v_velocity.dst_at_mut(row, column) = interpolation.run(old_x, old_y, v_velocity);
                     }
                 }
 
-                for row in 0..density.rows {
-                    for column in 0..density.columns {
-                        let x = column as f64 + density.x_offset;
-                        let y = row as f64 + density.y_offset;
+                for scalar in scalars.iter_mut() {
+                    for row in 0..scalar.rows {
+                        for column in 0..scalar.columns {
+                            let x = column as f64 + scalar.x_offset;
+                            let y = row as f64 + scalar.y_offset;
 
-                        let f1 = |_t: f64, y0: f64| -1.0 * interpolation.run(y0, y, &u_velocity) / timestep;
-                        let f2 = |_t: f64, y0: f64| -1.0 * interpolation.run(x, y0, &v_velocity) / timestep;
+                            let (old_x, old_y) = trace(x, y, u_velocity, v_velocity, timestep, interpolation, integration);
 
-                        let old_x: f64 = integration.run(0.0, x, &f1, timestep);
-                        let old_y: f64 = integration.run(0.0, y, &f2, timestep);
-
-                        *density.dst_at_mut(row, column) = interpolation.run(old_x, old_y, density);
+                            *scalar.dst_at_mut(row, column) = interpolation.run(old_x, old_y, scalar);
+                        }
                     }
                 }
 
                 u_velocity.swap_buffers();
                 v_velocity.swap_buffers();
-                density.swap_buffers();
+
+                for scalar in scalars.iter_mut() {
+                    scalar.swap_buffers();
+                }
             }
         }
     }
-}
\ No newline at end of file
+}
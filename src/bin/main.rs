@@ -1,6 +1,6 @@
 use rust_fluid::fluid_solver::FluidSolver;
 use rust_fluid::{integration, linear_solvers, advection};
-use rust_fluid::boundary::SolidBody;
+use rust_fluid::boundary::{DomainBoundary, EdgeCondition, SolidBody};
 
 extern crate image;
 
@@ -10,14 +10,16 @@ fn main() {
 
     let mut buffer = vec![0u8; width * height * 3];
 
-    let mut bodies = Vec::new();
-    bodies.push(SolidBody::new_box(1.25, 0.6, 0.6, 0.2, std::f64::consts::FRAC_PI_4, 0.0, 0.0, 0.0));
-    bodies.push(SolidBody::new_sphere(0.35, 0.5, 0.2, 0.0, 0.0, 0.0, 0.0));
+    let bodies = vec![
+        SolidBody::new_box(1.25, 0.6, 0.6, 0.2, std::f64::consts::FRAC_PI_4, 0.0, 0.0, 0.0),
+        SolidBody::new_sphere(0.35, 0.5, 0.2, 0.0, 0.0, 0.0, 0.0)
+    ];
 
     let mut solver = FluidSolver::new(height, width, 0.005, 1.0 / 300.0, 0.1, bodies)
         .integration(integration::Integration::BogackiShampine)
         .linear_solver(linear_solvers::LinearSolver::ConjugateGradient)
-        .advection(advection::Advection::SemiLagrangian);
+        .advection(advection::Advection::SemiLagrangian)
+        .domain_boundary(DomainBoundary::new(EdgeCondition::NoSlip, EdgeCondition::Open(0.0), EdgeCondition::NoSlip, EdgeCondition::NoSlip));
 
     // Over 10s
     for iteration in 0..500 {
@@ -0,0 +1,137 @@
+use super::SolidBody;
+
+// Number of points sampled around the surface of each body when looking for contacts
+const SURFACE_SAMPLES: usize = 48;
+
+// Deepest point of one body inside another, with the normal along which the first body has to move to separate
+struct Contact {
+    point: (f64, f64),
+    normal: (f64, f64),
+    depth: f64
+}
+
+// Finds the deepest sample of the surface of the first body inside the second
+fn deepest_inside(samples: &[(f64, f64)], other: &SolidBody) -> Option<Contact> {
+    samples.iter()
+        .map(|&(x, y)| (x, y, other.distance(x, y)))
+        .filter(|&(_, _, distance)| distance < 0.0)
+        .fold(None, |deepest: Option<(f64, f64, f64)>, sample| match deepest {
+            Some(deepest) if deepest.2 <= sample.2 => Some(deepest),
+            _ => Some(sample)
+        })
+        .map(|(x, y, distance)| Contact { point: (x, y), normal: other.distance_normal(x, y), depth: -distance })
+}
+
+// Pushes two overlapping bodies apart in proportion to their inverse masses and removes their approaching velocity
+// at the contact, keeping the given fraction of it. Bodies that do not respond to forces are never moved
+fn resolve(first: &mut SolidBody, second: Option<&mut SolidBody>, contact: &Contact, restitution: f64) {
+    let (first_mass, first_inertia) = first.inverse_mass();
+    let (second_mass, second_inertia) = second.as_ref().map_or((0.0, 0.0), |body| body.inverse_mass());
+
+    let total = first_mass + second_mass;
+    if total == 0.0 {
+        return;
+    }
+
+    let (normal_x, normal_y) = contact.normal;
+    let (x, y) = contact.point;
+
+    let (first_x, first_y) = first.position();
+    let first_arm = (y - first_y) * normal_x - (x - first_x) * normal_y;
+
+    let mut relative = first.velocity_x(x, y) * normal_x + first.velocity_y(x, y) * normal_y;
+    let mut second_arm = 0.0;
+
+    if let Some(body) = second.as_ref() {
+        let (second_x, second_y) = body.position();
+        second_arm = (y - second_y) * normal_x - (x - second_x) * normal_y;
+        relative -= body.velocity_x(x, y) * normal_x + body.velocity_y(x, y) * normal_y;
+    }
+
+    first.shift(normal_x * contact.depth * first_mass / total, normal_y * contact.depth * first_mass / total);
+
+    let impulse = if relative < 0.0 {
+        -(1.0 + restitution) * relative / (total + first_arm * first_arm * first_inertia + second_arm * second_arm * second_inertia)
+    } else {
+        0.0
+    };
+
+    first.apply_impulse(x, y, impulse * normal_x, impulse * normal_y);
+
+    if let Some(body) = second {
+        body.shift(-normal_x * contact.depth * second_mass / total, -normal_y * contact.depth * second_mass / total);
+        body.apply_impulse(x, y, -impulse * normal_x, -impulse * normal_y);
+    }
+}
+
+// Separates overlapping bodies and keeps them inside a width by height domain. Walls are ordered
+// [x = 0, x = max, y = 0, y = max] and only those marked true stop bodies, so open and periodic edges let them pass
+pub fn resolve_collisions(bodies: &mut [SolidBody], width: f64, height: f64, walls: [bool; 4], restitution: f64) {
+    // Sort and sweep over the bounding boxes along x to find the pairs that may touch
+    let boxes: Vec<(f64, f64, f64, f64)> = bodies.iter().map(|body| body.bounding_box()).collect();
+    let mut order: Vec<usize> = (0..bodies.len()).collect();
+    order.sort_by(|&a, &b| boxes[a].0.total_cmp(&boxes[b].0));
+
+    for (position, &first) in order.iter().enumerate() {
+        for &second in &order[(position + 1)..] {
+            if boxes[second].0 > boxes[first].2 {
+                break;
+            }
+
+            if boxes[second].1 > boxes[first].3 || boxes[first].1 > boxes[second].3 {
+                continue;
+            }
+
+            if bodies[first].inverse_mass().0 == 0.0 && bodies[second].inverse_mass().0 == 0.0 {
+                continue;
+            }
+
+            let forward = deepest_inside(&bodies[first].surface_points(SURFACE_SAMPLES), &bodies[second]);
+            let backward = deepest_inside(&bodies[second].surface_points(SURFACE_SAMPLES), &bodies[first]);
+
+            // Keep the deeper of the two, with its normal pointing from the second body towards the first
+            let contact = match (forward, backward) {
+                (Some(forward), Some(backward)) if backward.depth > forward.depth => Some(Contact { normal: (-backward.normal.0, -backward.normal.1), ..backward }),
+                (Some(forward), _) => Some(forward),
+                (None, Some(backward)) => Some(Contact { normal: (-backward.normal.0, -backward.normal.1), ..backward }),
+                (None, None) => None
+            };
+
+            if let Some(contact) = contact {
+                let (low, high) = bodies.split_at_mut(first.max(second));
+                let (a, b) = if first < second { (&mut low[first], &mut high[0]) } else { (&mut high[0], &mut low[second]) };
+                resolve(a, Some(b), &contact, restitution);
+            }
+        }
+    }
+
+    for body in bodies.iter_mut() {
+        if body.inverse_mass().0 == 0.0 {
+            continue;
+        }
+
+        let samples = body.surface_points(SURFACE_SAMPLES);
+
+        // Each wall is given by its inward normal and the offset at which the depth along that normal becomes positive
+        let checks = [
+            (walls[0], (1.0, 0.0), 0.0),
+            (walls[1], (-1.0, 0.0), -width),
+            (walls[2], (0.0, 1.0), 0.0),
+            (walls[3], (0.0, -1.0), -height)
+        ];
+
+        for &(enabled, normal, offset) in checks.iter() {
+            if !enabled {
+                continue;
+            }
+
+            let (depth, point) = samples.iter()
+                .map(|&(x, y)| (offset - normal.0 * x - normal.1 * y, (x, y)))
+                .fold((0.0, (0.0, 0.0)), |deepest, sample| if sample.0 > deepest.0 { sample } else { deepest });
+
+            if depth > 0.0 {
+                resolve(body, None, &Contact { point, normal, depth }, restitution);
+            }
+        }
+    }
+}
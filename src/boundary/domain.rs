@@ -0,0 +1,106 @@
+pub enum InflowProfile {
+    Uniform,
+    // Zero at both ends of the edge and peaking at its midpoint
+    Parabolic
+}
+
+pub enum EdgeCondition {
+    // Zero velocity on the wall
+    NoSlip,
+    // Zero normal velocity, tangential velocity unconstrained
+    FreeSlip,
+    // Fluid enters normal to the edge at the given speed, shaped by the profile
+    Inflow(f64, InflowProfile),
+    // Velocity is copied outward from the interior and the pressure gradient across the edge is zero
+    Outflow,
+    // Pressure outside the edge is held at the given value and the normal velocity follows from it
+    Pressure(f64),
+    // Pressure edge at the given reference pressure which clips inward velocity to zero before the projection
+    Open(f64),
+    // Fluid leaving through the edge re-enters through the opposite edge, which must also be periodic
+    Periodic
+}
+
+impl EdgeCondition {
+    // Returns the inward normal speed at a fraction of the way along an inflow edge
+    pub fn inflow_speed(&self, position: f64) -> Option<f64> {
+        match self {
+            EdgeCondition::NoSlip | EdgeCondition::FreeSlip => Some(0.0),
+            EdgeCondition::Inflow(speed, InflowProfile::Uniform) => Some(*speed),
+            EdgeCondition::Inflow(speed, InflowProfile::Parabolic) => Some(4.0 * speed * position * (1.0 - position)),
+            EdgeCondition::Outflow | EdgeCondition::Pressure(_) | EdgeCondition::Open(_) | EdgeCondition::Periodic => None
+        }
+    }
+
+    // Returns true where the normal velocity on the edge is copied from the interior rather than prescribed
+    pub fn is_open(&self) -> bool {
+        matches!(self, EdgeCondition::Outflow | EdgeCondition::Pressure(_) | EdgeCondition::Open(_))
+    }
+
+    // Returns true if the edge is a wall that bodies collide with
+    pub fn is_solid(&self) -> bool {
+        matches!(self, EdgeCondition::NoSlip | EdgeCondition::FreeSlip)
+    }
+
+    // Returns true if fluid may only leave through the edge
+    pub fn is_outlet(&self) -> bool {
+        matches!(self, EdgeCondition::Open(_))
+    }
+
+    // Returns the pressure held outside the edge, if any
+    pub fn pressure(&self) -> Option<f64> {
+        match self {
+            EdgeCondition::Pressure(pressure) | EdgeCondition::Open(pressure) => Some(*pressure),
+            _ => None
+        }
+    }
+
+    // Diagonal weight for diffusing tangential velocity against the edge. No-slip walls sit half a cell away
+    pub fn tangential_factor(&self) -> f64 {
+        match self {
+            EdgeCondition::NoSlip | EdgeCondition::Inflow(_, _) => 2.0,
+            _ => 0.0
+        }
+    }
+}
+
+// Conditions on the four edges of the domain. Top is the y = 0 edge, matching the first row of output images
+pub struct DomainBoundary {
+    pub left:   EdgeCondition,
+    pub right:  EdgeCondition,
+    pub top:    EdgeCondition,
+    pub bottom: EdgeCondition
+}
+
+impl DomainBoundary {
+    // Periodic edges have to be paired with the opposite edge, otherwise this panics
+    pub fn new(left: EdgeCondition, right: EdgeCondition, top: EdgeCondition, bottom: EdgeCondition) -> Self {
+        let periodic = |edge: &EdgeCondition| matches!(edge, EdgeCondition::Periodic);
+
+        if periodic(&left) != periodic(&right) || periodic(&top) != periodic(&bottom) {
+            panic!("Periodic edge conditions must be applied to opposite edges in pairs");
+        }
+
+        DomainBoundary {
+            left,
+            right,
+            top,
+            bottom
+        }
+    }
+
+    // A closed box with no-slip walls on every edge
+    pub fn closed() -> Self {
+        DomainBoundary::new(EdgeCondition::NoSlip, EdgeCondition::NoSlip, EdgeCondition::NoSlip, EdgeCondition::NoSlip)
+    }
+
+    // Returns true if fluid wraps from the right edge back to the left
+    pub fn periodic_x(&self) -> bool {
+        matches!(self.left, EdgeCondition::Periodic)
+    }
+
+    // Returns true if fluid wraps from the bottom edge back to the top
+    pub fn periodic_y(&self) -> bool {
+        matches!(self.top, EdgeCondition::Periodic)
+    }
+}
@@ -0,0 +1,66 @@
+use super::SolidBody;
+use crate::util::helper::clamp;
+
+// Uniform grid of square bins over the domain, each listing the bodies whose bounding box, grown by a margin,
+// overlaps it. Any body closer than the margin to a point is among the candidates of the bin holding that point
+pub struct BodyGrid {
+    bins: Vec<Vec<u32>>,
+    rows: usize,
+    columns: usize,
+    bin_size: f64
+}
+
+impl BodyGrid {
+    pub fn new(bodies: &[SolidBody], width: f64, height: f64, bin_size: f64, margin: f64) -> Self {
+        let rows = (height / bin_size).ceil().max(1.0) as usize;
+        let columns = (width / bin_size).ceil().max(1.0) as usize;
+
+        let mut grid = BodyGrid {
+            bins: vec![Vec::new(); rows * columns],
+            rows,
+            columns,
+            bin_size
+        };
+
+        for (index, body) in bodies.iter().enumerate() {
+            let (min_x, min_y, max_x, max_y) = body.bounding_box();
+
+            // Bodies wholly outside the grid cannot be near any point inside it
+            if max_x + margin < 0.0 || max_y + margin < 0.0 || min_x - margin > width || min_y - margin > height {
+                continue;
+            }
+
+            let (first_row, first_column) = grid.bin(min_x - margin, min_y - margin);
+            let (last_row, last_column) = grid.bin(max_x + margin, max_y + margin);
+
+            for row in first_row..=last_row {
+                for column in first_column..=last_column {
+                    grid.bins[row * columns + column].push(index as u32);
+                }
+            }
+        }
+
+        grid
+    }
+
+    fn bin(&self, x: f64, y: f64) -> (usize, usize) {
+        (clamp((y / self.bin_size).floor(), 0.0, (self.rows - 1) as f64) as usize,
+         clamp((x / self.bin_size).floor(), 0.0, (self.columns - 1) as f64) as usize)
+    }
+
+    // Indices of the bodies that may lie within the margin of a point
+    pub fn candidates(&self, x: f64, y: f64) -> &[u32] {
+        let (row, column) = self.bin(x, y);
+        &self.bins[row * self.columns + column]
+    }
+
+    // Signed distance to and index of the nearest body to a point, if any lies within the margin of it
+    pub fn nearest(&self, bodies: &[SolidBody], x: f64, y: f64) -> Option<(f64, u32)> {
+        self.candidates(x, y).iter()
+            .map(|&index| (bodies[index as usize].distance(x, y), index))
+            .fold(None, |best, candidate| match best {
+                Some(best) if best.0 <= candidate.0 => Some(best),
+                _ => Some(candidate)
+            })
+    }
+}
@@ -1,8 +1,90 @@
-use crate::util::helper::{length, max, rotate, nsgn};
+use crate::util::helper::{clamp, length, max, rotate, nsgn};
+
+mod collision;
+mod domain;
+mod grid;
+mod motion;
+mod porous;
+mod shapes;
+
+pub use self::collision::resolve_collisions;
+pub use self::domain::*;
+pub use self::grid::BodyGrid;
+pub use self::motion::*;
+pub use self::porous::PorousRegion;
+use self::shapes::{DistanceGrid, LocalSurface};
 
 pub enum SolidType {
     Box,
-    Sphere
+    Sphere,
+    Ellipse,
+    // Stadium along the local x axis, scale_x is the overall length and scale_y the diameter
+    Capsule,
+    // Box whose corners are rounded with the given radius
+    RoundedBox(f64),
+    // Simple polygon with vertices given in the body frame, scale_x and scale_y hold its bounding extents
+    Polygon(Vec<(f64, f64)>),
+    // Signed distance field sampled from a solid mask such as a bitmap image
+    Bitmap(DistanceGrid),
+    // Two operand bodies placed relative to this body's position and combined by a boolean operation
+    Csg(CsgOperation, Box<SolidBody>, Box<SolidBody>)
+}
+
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    // Removes the second operand from the first
+    Subtraction,
+    // Union that blends the two surfaces over the given distance
+    SmoothUnion(f64)
+}
+
+impl CsgOperation {
+    // Combines the signed distances of the operands, returning the distance and how much the first operand
+    // contributes to the surface, 1.0 where it alone defines it and 0.0 where the second one does
+    fn combine(&self, first: f64, second: f64) -> (f64, f64) {
+        match self {
+            CsgOperation::Union => if first <= second { (first, 1.0) } else { (second, 0.0) },
+            CsgOperation::Intersection => if first >= second { (first, 1.0) } else { (second, 0.0) },
+            CsgOperation::Subtraction => if first >= -second { (first, 1.0) } else { (-second, 0.0) },
+            CsgOperation::SmoothUnion(blend) => {
+                let weight = clamp(0.5 + 0.5 * (second - first) / max(*blend, 1e-12), 0.0, 1.0);
+                (weight * first + (1.0 - weight) * second - blend * weight * (1.0 - weight), weight)
+            }
+        }
+    }
+}
+
+pub enum WallModel {
+    // Fluid at the surface moves with the body
+    NoSlip,
+    // Only the normal velocity of the body is imposed, fluid slides freely along the surface
+    FreeSlip,
+    // Navier slip with the given slip length, between no-slip at zero and free-slip as it grows
+    PartialSlip(f64)
+}
+
+impl WallModel {
+    // Fraction of the fluid's tangential velocity relative to the body that is kept on faces a given depth inside it
+    pub fn slip_factor(&self, distance: f64) -> f64 {
+        match self {
+            WallModel::NoSlip => 0.0,
+            WallModel::FreeSlip => 1.0,
+            WallModel::PartialSlip(length) => length / (length + distance)
+        }
+    }
+}
+
+pub enum ThermalCondition {
+    // No heat crosses the surface
+    Adiabatic,
+    // Surface held at the given temperature
+    FixedTemperature(f64),
+    // Heat enters the fluid at the given rate per unit surface area, in temperature times length over time
+    HeatFlux(f64),
+    // Heat is conducted through the body with the given thermal diffusivity and conductivity relative to the fluid,
+    // starting from the given temperature
+    Conducting(f64, f64, f64)
 }
 
 pub struct SolidBody {
@@ -14,10 +96,15 @@ pub struct SolidBody {
     vel_x: f64,
     vel_y: f64,
     vel_theta: f64,
-    solid_type: SolidType
+    solid_type: SolidType,
+    wall: WallModel,
+    thermal: ThermalCondition,
+    motion: BodyMotion,
+    acceleration: (f64, f64, f64)
 }
 
 impl SolidBody {
+    #[allow(clippy::too_many_arguments)]
     pub fn new_box(pos_x: f64, pos_y: f64, scale_x: f64, scale_y: f64, theta: f64, vel_x: f64, vel_y: f64, vel_theta: f64) -> Self {
         SolidBody {
             pos_x,
@@ -28,7 +115,11 @@ impl SolidBody {
             vel_x,
             vel_y,
             vel_theta,
-            solid_type: SolidType::Box
+            solid_type: SolidType::Box,
+            wall: WallModel::NoSlip,
+            thermal: ThermalCondition::Adiabatic,
+            motion: BodyMotion::Prescribed,
+            acceleration: (0.0, 0.0, 0.0)
         }
     }
 
@@ -42,7 +133,283 @@ impl SolidBody {
             vel_x,
             vel_y,
             vel_theta,
-            solid_type: SolidType::Sphere
+            solid_type: SolidType::Sphere,
+            wall: WallModel::NoSlip,
+            thermal: ThermalCondition::Adiabatic,
+            motion: BodyMotion::Prescribed,
+            acceleration: (0.0, 0.0, 0.0)
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_ellipse(pos_x: f64, pos_y: f64, scale_x: f64, scale_y: f64, theta: f64, vel_x: f64, vel_y: f64, vel_theta: f64) -> Self {
+        SolidBody {
+            solid_type: SolidType::Ellipse,
+            ..SolidBody::new_box(pos_x, pos_y, scale_x, scale_y, theta, vel_x, vel_y, vel_theta)
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_capsule(pos_x: f64, pos_y: f64, scale_x: f64, scale_y: f64, theta: f64, vel_x: f64, vel_y: f64, vel_theta: f64) -> Self {
+        SolidBody {
+            solid_type: SolidType::Capsule,
+            ..SolidBody::new_box(pos_x, pos_y, scale_x.max(scale_y), scale_y, theta, vel_x, vel_y, vel_theta)
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_rounded_box(pos_x: f64, pos_y: f64, scale_x: f64, scale_y: f64, radius: f64, theta: f64, vel_x: f64, vel_y: f64, vel_theta: f64) -> Self {
+        SolidBody {
+            solid_type: SolidType::RoundedBox(radius),
+            ..SolidBody::new_box(pos_x, pos_y, scale_x, scale_y, theta, vel_x, vel_y, vel_theta)
+        }
+    }
+
+    // Polygon with at least three vertices around pos_x, pos_y, in either winding order
+    pub fn new_polygon(pos_x: f64, pos_y: f64, vertices: Vec<(f64, f64)>, theta: f64, vel_x: f64, vel_y: f64, vel_theta: f64) -> Self {
+        if vertices.len() < 3 {
+            panic!("A polygon needs at least three vertices, got {}", vertices.len());
+        }
+
+        let extent_x = vertices.iter().fold(0.0, |extent, vertex| max(extent, vertex.0.abs()));
+        let extent_y = vertices.iter().fold(0.0, |extent, vertex| max(extent, vertex.1.abs()));
+
+        SolidBody {
+            solid_type: SolidType::Polygon(vertices),
+            ..SolidBody::new_box(pos_x, pos_y, 2.0 * extent_x, 2.0 * extent_y, theta, vel_x, vel_y, vel_theta)
+        }
+    }
+
+    // NACA 4-digit airfoil such as "0012" or "2412" with its quarter chord point at pos_x, pos_y. At zero theta the
+    // chord runs along +x with the cambered side towards -y, which is up in the images
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_airfoil(pos_x: f64, pos_y: f64, code: &str, chord: f64, theta: f64, vel_x: f64, vel_y: f64, vel_theta: f64) -> Self {
+        let vertices = shapes::naca_four_digit(code, 80)
+            .into_iter()
+            .map(|(x, y)| ((x - 0.25) * chord, y * chord))
+            .collect();
+
+        SolidBody::new_polygon(pos_x, pos_y, vertices, theta, vel_x, vel_y, vel_theta)
+    }
+
+    // Static body from a row major mask where true marks solid pixels, centred on pos_x, pos_y with each pixel
+    // covering pixel_size in both directions. Row 0 of the mask is the top of the body, like in the output images
+    pub fn new_bitmap(pos_x: f64, pos_y: f64, mask: &[bool], width: usize, height: usize, pixel_size: f64) -> Self {
+        let grid = DistanceGrid::new(mask, width, height, pixel_size);
+
+        SolidBody {
+            solid_type: SolidType::Bitmap(grid),
+            ..SolidBody::new_box(pos_x, pos_y, width as f64 * pixel_size, height as f64 * pixel_size, 0.0, 0.0, 0.0, 0.0)
+        }
+    }
+
+    // Loads a static body from an image file where dark pixels, with a luminance below half, are solid
+    pub fn from_image(path: &str, pos_x: f64, pos_y: f64, pixel_size: f64) -> image::ImageResult<Self> {
+        let image = image::open(path)?.to_luma();
+        let (width, height) = image.dimensions();
+        let mask: Vec<bool> = image.pixels().map(|pixel| pixel.0[0] < 128).collect();
+
+        Ok(SolidBody::new_bitmap(pos_x, pos_y, &mask, width as usize, height as usize, pixel_size))
+    }
+
+    // Combines two bodies into one that moves and rotates as a whole. The result takes its position, motion, wall
+    // model and thermal condition from the first operand, and both operands keep their current placement relative to it
+    pub fn combine(operation: CsgOperation, first: SolidBody, mut second: SolidBody) -> Self {
+        let pos_x = first.pos_x;
+        let pos_y = first.pos_y;
+        let theta = first.theta;
+
+        let mut first = first;
+        for operand in [&mut first, &mut second] {
+            operand.pos_x -= pos_x;
+            operand.pos_y -= pos_y;
+            rotate(&mut operand.pos_x, &mut operand.pos_y, -theta);
+            operand.theta -= theta;
+        }
+
+        let reach = |operand: &SolidBody| length(operand.pos_x, operand.pos_y) + 0.5 * length(operand.scale_x, operand.scale_y);
+        let scale = 2.0 * max(reach(&first), reach(&second));
+
+        SolidBody {
+            pos_x,
+            pos_y,
+            scale_x: scale,
+            scale_y: scale,
+            theta,
+            vel_x: first.vel_x,
+            vel_y: first.vel_y,
+            vel_theta: first.vel_theta,
+            wall: std::mem::replace(&mut first.wall, WallModel::NoSlip),
+            thermal: std::mem::replace(&mut first.thermal, ThermalCondition::Adiabatic),
+            motion: std::mem::replace(&mut first.motion, BodyMotion::Prescribed),
+            acceleration: (0.0, 0.0, 0.0),
+            solid_type: SolidType::Csg(operation, Box::new(first), Box::new(second))
+        }
+    }
+
+    pub fn union(first: SolidBody, second: SolidBody) -> Self {
+        SolidBody::combine(CsgOperation::Union, first, second)
+    }
+
+    pub fn intersection(first: SolidBody, second: SolidBody) -> Self {
+        SolidBody::combine(CsgOperation::Intersection, first, second)
+    }
+
+    pub fn subtraction(first: SolidBody, second: SolidBody) -> Self {
+        SolidBody::combine(CsgOperation::Subtraction, first, second)
+    }
+
+    pub fn smooth_union(first: SolidBody, second: SolidBody, blend: f64) -> Self {
+        SolidBody::combine(CsgOperation::SmoothUnion(blend), first, second)
+    }
+
+    // Sets how fluid velocity behaves along the surface of the body
+    pub fn wall_model(mut self, wall: WallModel) -> Self {
+        self.wall = wall;
+        self
+    }
+
+    // Sets how the body exchanges heat with the temperature field of the fluid
+    pub fn thermal_condition(mut self, thermal: ThermalCondition) -> Self {
+        self.thermal = thermal;
+        self
+    }
+
+    // Lets the flow move the body as a rigid body with the given mass and moment of inertia per unit depth
+    pub fn rigid(mut self, mass: f64, inertia: f64) -> Self {
+        self.motion = BodyMotion::Free(mass, inertia);
+        self
+    }
+
+    // Moves the body through keyframes of (time, (x, y, theta)), which must be sorted by time
+    pub fn keyframes(mut self, frames: Vec<(f64, Pose)>, interpolation: PathInterpolation) -> Self {
+        if frames.is_empty() {
+            panic!("Keyframed motion needs at least one keyframe");
+        }
+
+        if !frames.windows(2).all(|pair| pair[0].0 <= pair[1].0) {
+            panic!("Keyframes must be sorted by time");
+        }
+
+        self.motion = BodyMotion::Keyframes(frames, interpolation);
+        self
+    }
+
+    // Heaves the body sinusoidally in y and pitches it about its current pose. The pitch leads the heave by phase
+    pub fn oscillate(mut self, heave: f64, pitch: f64, frequency: f64, phase: f64) -> Self {
+        self.motion = BodyMotion::Oscillation((self.pos_x, self.pos_y, self.theta), heave, pitch, frequency, phase);
+        self
+    }
+
+    // Moves the body along a pose (x, y, theta) and velocity (x, y, theta) returned by a function of time
+    pub fn scripted(mut self, f: Box<dyn Fn(f64) -> (Pose, Pose)>) -> Self {
+        self.motion = BodyMotion::Scripted(f);
+        self
+    }
+
+    // Lets the flow move the body, taking its mass and moment of inertia from a uniform density over its shape
+    pub fn solid_density(self, density: f64) -> Self {
+        let samples = 128;
+        let reach = 0.5 * length(self.scale_x, self.scale_y);
+        let spacing = 2.0 * reach / samples as f64;
+
+        let mut area = 0.0;
+        let mut second_moment = 0.0;
+
+        for row in 0..samples {
+            for column in 0..samples {
+                let x = (column as f64 + 0.5) * spacing - reach;
+                let y = (row as f64 + 0.5) * spacing - reach;
+
+                if self.distance(self.pos_x + x, self.pos_y + y) < 0.0 {
+                    area += spacing * spacing;
+                    second_moment += (x * x + y * y) * spacing * spacing;
+                }
+            }
+        }
+
+        self.rigid(density * area, density * second_moment)
+    }
+
+    pub fn position(&self) -> (f64, f64) {
+        (self.pos_x, self.pos_y)
+    }
+
+    // Axis aligned box (min x, min y, max x, max y) holding the body in any orientation
+    pub fn bounding_box(&self) -> (f64, f64, f64, f64) {
+        let reach = 0.5 * length(self.scale_x, self.scale_y);
+        (self.pos_x - reach, self.pos_y - reach, self.pos_x + reach, self.pos_y + reach)
+    }
+
+    // Returns how the body exchanges heat with the fluid
+    pub fn thermal(&self) -> &ThermalCondition {
+        &self.thermal
+    }
+
+    // Returns how fluid moves along the surface of the body
+    pub fn wall(&self) -> &WallModel {
+        &self.wall
+    }
+
+    // Inverse mass and moment of inertia, both zero for bodies that do not respond to forces
+    pub fn inverse_mass(&self) -> (f64, f64) {
+        match self.motion {
+            BodyMotion::Free(mass, inertia) => (1.0 / mass, 1.0 / inertia),
+            _ => (0.0, 0.0)
+        }
+    }
+
+    // Moves the body without changing its velocity
+    pub fn shift(&mut self, x: f64, y: f64) {
+        self.pos_x += x;
+        self.pos_y += y;
+    }
+
+    // Changes the velocity of a free body by an impulse applied at a point
+    pub fn apply_impulse(&mut self, x: f64, y: f64, impulse_x: f64, impulse_y: f64) {
+        let (mass, inertia) = self.inverse_mass();
+
+        self.vel_x += impulse_x * mass;
+        self.vel_y += impulse_y * mass;
+        self.vel_theta += self.torque(x, y, impulse_x, impulse_y) * inertia;
+    }
+
+    // Points on the surface found by projecting a circle around the body onto it
+    pub fn surface_points(&self, count: usize) -> Vec<(f64, f64)> {
+        let reach = 0.5 * length(self.scale_x, self.scale_y);
+
+        (0..count).map(|i| {
+            let angle = 2.0 * std::f64::consts::PI * i as f64 / count as f64;
+            let mut x = self.pos_x + reach * angle.cos();
+            let mut y = self.pos_y + reach * angle.sin();
+            self.closest_surface_point(&mut x, &mut y);
+            (x, y)
+        }).collect()
+    }
+
+    // Torque about the centre of the body from a force applied at a point, positive in the direction theta increases
+    pub fn torque(&self, x: f64, y: f64, force_x: f64, force_y: f64) -> f64 {
+        (y - self.pos_y) * force_x - (x - self.pos_x) * force_y
+    }
+
+    // Integrates the velocity of a free body over one timestep under the given force, torque and acceleration
+    pub fn accelerate(&mut self, force_x: f64, force_y: f64, torque: f64, acceleration: (f64, f64), fluid_density: f64, timestep: f64) {
+        if let BodyMotion::Free(mass, inertia) = self.motion {
+            let reach = 0.5 * length(self.scale_x, self.scale_y);
+            let added_mass = fluid_density * std::f64::consts::PI * reach * reach;
+            let added_inertia = 0.125 * added_mass * reach * reach;
+
+            let (previous_x, previous_y, previous_theta) = self.acceleration;
+
+            self.acceleration = (
+                (force_x + mass * acceleration.0 + added_mass * previous_x) / (mass + added_mass),
+                (force_y + mass * acceleration.1 + added_mass * previous_y) / (mass + added_mass),
+                (torque + added_inertia * previous_theta) / (inertia + added_inertia)
+            );
+
+            self.vel_x += timestep * self.acceleration.0;
+            self.vel_y += timestep * self.acceleration.1;
+            self.vel_theta += timestep * self.acceleration.2;
         }
     }
 
@@ -63,7 +430,7 @@ impl SolidBody {
     }
 
     pub fn velocity_x(&self, _x: f64, y: f64) -> f64 {
-        (self.pos_y - y) * self.vel_theta + self.vel_x
+        (y - self.pos_y) * self.vel_theta + self.vel_x
     }
 
     pub fn velocity_y(&self, x: f64, _y: f64) -> f64 {
@@ -75,14 +442,92 @@ impl SolidBody {
         *vy = self.velocity_y(x, y);
     }
 
-    pub fn update(&mut self, timestep: f64) {
-        self.pos_x += self.vel_x * timestep;
-        self.pos_y += self.vel_y * timestep;
-        self.theta += self.vel_theta * timestep;
+    // Velocity imposed on a face inside the body, given the fluid velocity extrapolated onto it. The normal component
+    // always follows the body while the tangential component keeps the slip fraction of the fluid's relative motion
+    pub fn wall_velocity(&self, x: f64, y: f64, fluid_x: f64, fluid_y: f64, distance: f64) -> (f64, f64) {
+        let body_x = self.velocity_x(x, y);
+        let body_y = self.velocity_y(x, y);
+        let slip = self.wall.slip_factor(distance);
+
+        if slip == 0.0 {
+            return (body_x, body_y);
+        }
+
+        let (normal_x, normal_y) = self.distance_normal(x, y);
+        let relative_x = fluid_x - body_x;
+        let relative_y = fluid_y - body_y;
+        let normal = relative_x * normal_x + relative_y * normal_y;
+
+        (body_x + slip * (relative_x - normal * normal_x), body_y + slip * (relative_y - normal * normal_y))
+    }
+
+    // Queries the analytic shapes in the unscaled body frame, where their distances stay exact
+    fn local_surface(&self, mut x: f64, mut y: f64) -> LocalSurface {
+        x -= self.pos_x;
+        y -= self.pos_y;
+        rotate(&mut x, &mut y, -self.theta);
+
+        match &self.solid_type {
+            SolidType::Capsule => shapes::capsule(x, y, self.scale_x, 0.5 * self.scale_y),
+            SolidType::RoundedBox(radius) => shapes::rounded_box(x, y, 0.5 * self.scale_x, 0.5 * self.scale_y, *radius),
+            SolidType::Polygon(vertices) => shapes::polygon(x, y, vertices),
+            SolidType::Bitmap(grid) => grid.surface(x, y),
+            SolidType::Csg(operation, first, second) => {
+                let surface = |x: f64, y: f64| {
+                    let (distance, weight) = operation.combine(first.distance(x, y), second.distance(x, y));
+                    let (first_x, first_y) = first.distance_normal(x, y);
+                    let (mut second_x, mut second_y) = second.distance_normal(x, y);
+
+                    if let CsgOperation::Subtraction = operation {
+                        second_x = -second_x;
+                        second_y = -second_y;
+                    }
+
+                    let normal_x = weight * first_x + (1.0 - weight) * second_x;
+                    let normal_y = weight * first_y + (1.0 - weight) * second_y;
+                    let n = length(normal_x, normal_y);
+
+                    // Opposing normals cancel out on the medial axis of a smooth union, pick the dominant operand there
+                    if n < 1e-6 {
+                        (distance, if weight >= 0.5 { (first_x, first_y) } else { (second_x, second_y) })
+                    } else {
+                        (distance, (normal_x / n, normal_y / n))
+                    }
+                };
+
+                let (distance, normal) = surface(x, y);
+
+                // Combined distances are only bounds away from the surface, so walk down the gradient a few times
+                let mut closest = (x, y);
+                for _iteration in 0..4 {
+                    let (step, (step_x, step_y)) = surface(closest.0, closest.1);
+                    closest = (closest.0 - step * step_x, closest.1 - step * step_y);
+                }
+
+                LocalSurface { distance, closest, normal }
+            },
+            _ => shapes::ellipse(x, y, 0.5 * self.scale_x, 0.5 * self.scale_y)
+        }
+    }
+
+    // Advances the body from time to time + timestep, either along its motion profile or with its current velocity
+    pub fn update(&mut self, time: f64, timestep: f64) {
+        if let Some(((x, y, theta), (vel_x, vel_y, vel_theta))) = self.motion.sample(time + timestep) {
+            self.pos_x = x;
+            self.pos_y = y;
+            self.theta = theta;
+            self.vel_x = vel_x;
+            self.vel_y = vel_y;
+            self.vel_theta = vel_theta;
+        } else {
+            self.pos_x += self.vel_x * timestep;
+            self.pos_y += self.vel_y * timestep;
+            self.theta += self.vel_theta * timestep;
+        }
     }
 
     pub fn distance(&self, mut x: f64, mut y: f64) -> f64 {
-        match self.solid_type {
+        match &self.solid_type {
             SolidType::Box => {
                 x -= self.pos_x;
                 y -= self.pos_y;
@@ -98,12 +543,13 @@ impl SolidBody {
             },
             SolidType::Sphere => {
                 length(x - self.pos_x, y - self.pos_y) - self.scale_x * 0.5
-            }
+            },
+            _ => self.local_surface(x, y).distance
         }
     }
 
     pub fn closest_surface_point(&self, x: &mut f64, y: &mut f64) {
-        match self.solid_type {
+        match &self.solid_type {
             SolidType::Box => {
                 *x -= self.pos_x;
                 *y -= self.pos_y;
@@ -134,6 +580,13 @@ impl SolidBody {
                 }
 
                 self.local_to_global(x, y);
+            },
+            _ => {
+                (*x, *y) = self.local_surface(*x, *y).closest;
+
+                rotate(x, y, self.theta);
+                *x += self.pos_x;
+                *y += self.pos_y;
             }
         }
     }
@@ -141,7 +594,7 @@ impl SolidBody {
     pub fn distance_normal(&self, mut x: f64, mut y: f64) -> (f64, f64) {
         let mut normal_x: f64;
         let mut normal_y: f64;
-        match self.solid_type {
+        match &self.solid_type {
             SolidType::Box => {
                 x -= self.pos_x;
                 y -= self.pos_y;
@@ -172,9 +625,104 @@ impl SolidBody {
                     normal_y = y/r;
                 }
 
+                (normal_x, normal_y)
+            },
+            _ => {
+                (normal_x, normal_y) = self.local_surface(x, y).normal;
+
+                rotate(&mut normal_x, &mut normal_y, self.theta);
+
                 (normal_x, normal_y)
             }
         }
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!((actual - expected).abs() < tolerance, "{} is not within {} of {}", actual, tolerance, expected);
+    }
+
+    fn circle(x: f64, y: f64, radius: f64) -> SolidBody {
+        SolidBody::new_sphere(x, y, 2.0 * radius, 0.0, 0.0, 0.0, 0.0)
+    }
+
+    #[test]
+    fn operations_pick_the_defining_operand() {
+        assert_eq!(CsgOperation::Union.combine(-1.0, 2.0), (-1.0, 1.0));
+        assert_eq!(CsgOperation::Union.combine(3.0, 2.0), (2.0, 0.0));
+        assert_eq!(CsgOperation::Intersection.combine(-1.0, 2.0), (2.0, 0.0));
+        assert_eq!(CsgOperation::Subtraction.combine(-1.0, -2.0), (2.0, 0.0));
+        assert_eq!(CsgOperation::Subtraction.combine(1.0, 3.0), (1.0, 1.0));
+    }
+
+    #[test]
+    fn smooth_union_only_blends_near_both_surfaces() {
+        let blend = CsgOperation::SmoothUnion(0.5);
+
+        assert_eq!(blend.combine(-1.0, 2.0), (-1.0, 1.0));
+        assert_eq!(blend.combine(2.0, -1.0), (-1.0, 0.0));
+
+        let (distance, weight) = blend.combine(0.1, 0.1);
+        assert_close(distance, 0.1 - 0.125, 1e-12);
+        assert_close(weight, 0.5, 1e-12);
+    }
+
+    #[test]
+    fn union_of_circles_takes_the_nearer_surface() {
+        let body = SolidBody::union(circle(1.0, 1.0, 0.5), circle(2.0, 1.0, 0.5));
+
+        assert_close(body.distance(0.0, 1.0), 0.5, 1e-9);
+        assert_close(body.distance(3.0, 1.0), 0.5, 1e-9);
+        assert_close(body.distance(1.5, 1.0), 0.0, 1e-9);
+        assert_close(body.distance(2.0, 1.0), -0.5, 1e-9);
+    }
+
+    #[test]
+    fn intersection_of_circles_keeps_the_lens() {
+        let body = SolidBody::intersection(circle(1.0, 1.0, 0.5), circle(1.5, 1.0, 0.5));
+
+        assert_close(body.distance(1.25, 1.0), -0.25, 1e-9);
+        assert_close(body.distance(0.75, 1.0), 0.25, 1e-9);
+    }
+
+    #[test]
+    fn subtraction_leaves_a_ring() {
+        let body = SolidBody::subtraction(circle(1.0, 1.0, 0.5), circle(1.0, 1.0, 0.25));
+
+        assert_close(body.distance(1.0, 1.0), 0.25, 1e-9);
+        assert_close(body.distance(1.375, 1.0), -0.125, 1e-9);
+        assert_close(body.distance(2.0, 1.0), 0.5, 1e-9);
+
+        let (normal_x, normal_y) = body.distance_normal(1.1, 1.0);
+        assert_close(normal_x, -1.0, 1e-9);
+        assert_close(normal_y, 0.0, 1e-9);
+    }
+
+    #[test]
+    fn combined_body_moves_and_turns_as_a_whole() {
+        let mut body = SolidBody::union(circle(1.0, 1.0, 0.25), circle(2.0, 1.0, 0.25));
+        body.pos_x += 1.0;
+        body.theta = 0.5 * std::f64::consts::PI;
+
+        // A quarter turn carries the second circle from the right of the first to above it, towards -y
+        assert_close(body.distance(2.0, 1.0), -0.25, 1e-9);
+        assert_close(body.distance(2.0, 0.0), -0.25, 1e-9);
+        assert_close(body.distance(3.0, 1.0), 0.75, 1e-9);
+    }
+
+    #[test]
+    fn closest_point_of_a_combination_is_on_its_surface() {
+        let body = SolidBody::smooth_union(circle(1.0, 1.0, 0.5), circle(1.8, 1.0, 0.4), 0.2);
+
+        for &(x, y) in &[(0.2, 1.0), (1.4, 1.8), (2.6, 0.9), (1.0, 1.1)] {
+            let (mut surface_x, mut surface_y) = (x, y);
+            body.closest_surface_point(&mut surface_x, &mut surface_y);
+            assert_close(body.distance(surface_x, surface_y), 0.0, 1e-3);
+        }
+    }
+}
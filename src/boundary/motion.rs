@@ -0,0 +1,179 @@
+use std::f64::consts::PI;
+
+// Position and angle of a body as (x, y, theta)
+pub type Pose = (f64, f64, f64);
+
+pub enum PathInterpolation {
+    // Straight segments between keyframes, with the velocity jumping at each keyframe
+    Linear,
+    // Catmull-Rom spline through the keyframes, with a continuous velocity
+    Smooth
+}
+
+pub enum BodyMotion {
+    // Moves with the velocity it was given, unaffected by the flow
+    Prescribed,
+    // Rigid body with the given mass and moment of inertia, accelerated by fluid and body forces
+    Free(f64, f64),
+    // Follows (time, x, y, theta) keyframes sorted by time, holding the first and last pose outside them
+    Keyframes(Vec<(f64, Pose)>, PathInterpolation),
+    // Heaves in y and pitches about a base pose with the given heave amplitude, pitch amplitude, frequency and phase of
+    // the pitch ahead of the heave
+    Oscillation(Pose, f64, f64, f64, f64),
+    // Pose and velocity given by a function of time
+    Scripted(Box<dyn Fn(f64) -> (Pose, Pose)>)
+}
+
+impl BodyMotion {
+    // Pose and velocity at the given time for motions that are a function of time, None for the others
+    pub fn sample(&self, time: f64) -> Option<(Pose, Pose)> {
+        match self {
+            BodyMotion::Prescribed | BodyMotion::Free(_, _) => None,
+            BodyMotion::Keyframes(frames, interpolation) => Some(sample_keyframes(frames, interpolation, time)),
+            BodyMotion::Oscillation((x, y, theta), heave, pitch, frequency, phase) => {
+                let omega = 2.0 * PI * frequency;
+
+                Some(((*x, y + heave * (omega * time).sin(), theta + pitch * (omega * time + phase).sin()),
+                      (0.0, heave * omega * (omega * time).cos(), pitch * omega * (omega * time + phase).cos())))
+            },
+            BodyMotion::Scripted(f) => Some(f(time))
+        }
+    }
+}
+
+fn scale(pose: Pose, factor: f64) -> Pose {
+    (pose.0 * factor, pose.1 * factor, pose.2 * factor)
+}
+
+fn add(a: Pose, b: Pose) -> Pose {
+    (a.0 + b.0, a.1 + b.1, a.2 + b.2)
+}
+
+fn subtract(a: Pose, b: Pose) -> Pose {
+    (a.0 - b.0, a.1 - b.1, a.2 - b.2)
+}
+
+fn sample_keyframes(frames: &[(f64, Pose)], interpolation: &PathInterpolation, time: f64) -> (Pose, Pose) {
+    let last = frames.len() - 1;
+
+    if time <= frames[0].0 {
+        return (frames[0].1, (0.0, 0.0, 0.0));
+    }
+
+    if time >= frames[last].0 {
+        return (frames[last].1, (0.0, 0.0, 0.0));
+    }
+
+    let segment = frames.iter().rposition(|frame| frame.0 <= time).unwrap();
+    let (start_time, start) = frames[segment];
+    let (end_time, end) = frames[segment + 1];
+    let duration = end_time - start_time;
+    let s = (time - start_time) / duration;
+
+    match interpolation {
+        PathInterpolation::Linear => {
+            let change = subtract(end, start);
+            (add(start, scale(change, s)), scale(change, 1.0 / duration))
+        },
+        PathInterpolation::Smooth => {
+            // Tangents from the neighbouring keyframes, falling back to the segment itself at the ends
+            let tangent = |index: usize| {
+                let before = if index > 0 { index - 1 } else { index };
+                let after = if index < last { index + 1 } else { index };
+                scale(subtract(frames[after].1, frames[before].1), duration / (frames[after].0 - frames[before].0))
+            };
+
+            let start_tangent = tangent(segment);
+            let end_tangent = tangent(segment + 1);
+
+            let s2 = s * s;
+            let s3 = s2 * s;
+
+            let pose = add(add(scale(start, 2.0 * s3 - 3.0 * s2 + 1.0), scale(start_tangent, s3 - 2.0 * s2 + s)),
+                           add(scale(end, -2.0 * s3 + 3.0 * s2), scale(end_tangent, s3 - s2)));
+
+            let rate = add(add(scale(start, 6.0 * s2 - 6.0 * s), scale(start_tangent, 3.0 * s2 - 4.0 * s + 1.0)),
+                           add(scale(end, -6.0 * s2 + 6.0 * s), scale(end_tangent, 3.0 * s2 - 2.0 * s)));
+
+            (pose, scale(rate, 1.0 / duration))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boundary::SolidBody;
+
+    fn assert_pose(actual: Pose, expected: Pose, tolerance: f64) {
+        let error = subtract(actual, expected);
+        assert!(error.0.abs().max(error.1.abs()).max(error.2.abs()) < tolerance,
+                "{:?} is not within {} of {:?}", actual, tolerance, expected);
+    }
+
+    fn frames() -> Vec<(f64, Pose)> {
+        vec![(0.0, (0.0, 0.0, 0.0)), (1.0, (1.0, 2.0, 0.5)), (3.0, (2.0, 2.0, -0.5)), (4.0, (4.0, 0.0, 0.0))]
+    }
+
+    #[test]
+    fn linear_keyframes_move_at_a_constant_rate_between_frames() {
+        let (pose, velocity) = sample_keyframes(&frames(), &PathInterpolation::Linear, 2.5);
+
+        assert_pose(pose, (1.75, 2.0, -0.25), 1e-12);
+        assert_pose(velocity, (0.5, 0.0, -0.5), 1e-12);
+    }
+
+    #[test]
+    fn keyframes_hold_still_outside_their_times() {
+        for interpolation in [PathInterpolation::Linear, PathInterpolation::Smooth] {
+            assert_pose(sample_keyframes(&frames(), &interpolation, -1.0).0, (0.0, 0.0, 0.0), 1e-12);
+            assert_pose(sample_keyframes(&frames(), &interpolation, -1.0).1, (0.0, 0.0, 0.0), 1e-12);
+            assert_pose(sample_keyframes(&frames(), &interpolation, 5.0).0, (4.0, 0.0, 0.0), 1e-12);
+            assert_pose(sample_keyframes(&frames(), &interpolation, 5.0).1, (0.0, 0.0, 0.0), 1e-12);
+        }
+    }
+
+    #[test]
+    fn smooth_keyframes_pass_through_every_frame() {
+        for (time, pose) in frames() {
+            assert_pose(sample_keyframes(&frames(), &PathInterpolation::Smooth, time).0, pose, 1e-12);
+        }
+    }
+
+    #[test]
+    fn smooth_keyframe_velocity_is_the_rate_of_the_pose() {
+        let step = 1e-6;
+
+        for time in [0.3, 0.999, 1.001, 2.0, 2.999, 3.001, 3.7] {
+            let before = sample_keyframes(&frames(), &PathInterpolation::Smooth, time - step).0;
+            let after = sample_keyframes(&frames(), &PathInterpolation::Smooth, time + step).0;
+            let velocity = sample_keyframes(&frames(), &PathInterpolation::Smooth, time).1;
+
+            assert_pose(velocity, scale(subtract(after, before), 0.5 / step), 1e-4);
+        }
+    }
+
+    #[test]
+    fn smooth_keyframes_follow_a_uniform_motion_exactly() {
+        let uniform = vec![(0.0, (0.0, 0.0, 0.0)), (1.0, (1.0, -2.0, 0.5)), (2.0, (2.0, -4.0, 1.0))];
+        let (pose, velocity) = sample_keyframes(&uniform, &PathInterpolation::Smooth, 0.5);
+
+        assert_pose(pose, (0.5, -1.0, 0.25), 1e-12);
+        assert_pose(velocity, (1.0, -2.0, 0.5), 1e-12);
+    }
+
+    #[test]
+    fn keyframed_motion_samples_its_frames() {
+        let motion = BodyMotion::Keyframes(frames(), PathInterpolation::Linear);
+
+        assert_pose(motion.sample(0.5).unwrap().0, (0.5, 1.0, 0.25), 1e-12);
+        assert!(BodyMotion::Prescribed.sample(0.5).is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn keyframes_must_be_sorted() {
+        let _ = SolidBody::new_sphere(0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0)
+            .keyframes(vec![(1.0, (0.0, 0.0, 0.0)), (0.0, (1.0, 0.0, 0.0))], PathInterpolation::Linear);
+    }
+}
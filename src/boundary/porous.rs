@@ -0,0 +1,56 @@
+use crate::boundary::SolidBody;
+use crate::util::fluid_quantity::FluidQuantity;
+use crate::util::helper::{clamp, max, min};
+
+// A region of the fluid filled with a porous medium such as a filter, screen or canopy. Flow passes through it but is
+// slowed by a Darcy drag proportional to its velocity relative to the region, following the Brinkman equations
+pub struct PorousRegion {
+    pub body: SolidBody,
+    pub permeability: f64
+}
+
+impl PorousRegion {
+    // Creates a porous region covering the shape of the body. Permeability is in squared length units, smaller values
+    // resist the flow more strongly
+    pub fn new(body: SolidBody, permeability: f64) -> Self {
+        PorousRegion { body, permeability }
+    }
+
+    // Fraction of the face at (x, y) lying inside the region, smoothed over one cell so the drag does not switch on
+    // abruptly as the region moves across the grid
+    fn fraction(&self, x: f64, y: f64, cell_size: f64) -> f64 {
+        clamp(0.5 - self.body.distance(x, y) / cell_size, 0.0, 1.0)
+    }
+
+    // Applies the Darcy drag of the region to every fluid face of a velocity component it covers. The drag is
+    // integrated implicitly, so the velocity relaxes towards that of the region without overshooting however small the
+    // permeability. Faces are only visited within the bounding box of the region
+    pub fn apply(&self, velocity: &mut FluidQuantity, horizontal: bool, viscosity: f64, timestep: f64) {
+        let cell_size = velocity.cell_size;
+        let rate = viscosity / self.permeability * timestep;
+        let (min_x, min_y, max_x, max_y) = self.body.bounding_box();
+
+        let first_column = max(((min_x - cell_size) / cell_size - velocity.x_offset).floor(), 0.0) as usize;
+        let first_row = max(((min_y - cell_size) / cell_size - velocity.y_offset).floor(), 0.0) as usize;
+        let last_column = min(((max_x + cell_size) / cell_size - velocity.x_offset).ceil(), (velocity.columns - 1) as f64) as usize;
+        let last_row = min(((max_y + cell_size) / cell_size - velocity.y_offset).ceil(), (velocity.rows - 1) as f64) as usize;
+
+        for row in first_row..=last_row {
+            for column in first_column..=last_column {
+                if velocity.cell_at(row, column) != 0 {
+                    continue;
+                }
+
+                let x = (column as f64 + velocity.x_offset) * cell_size;
+                let y = (row as f64 + velocity.y_offset) * cell_size;
+                let fraction = self.fraction(x, y, cell_size);
+
+                if fraction > 0.0 {
+                    let region = if horizontal { self.body.velocity_x(x, y) } else { self.body.velocity_y(x, y) };
+                    let value = velocity.at_mut(row, column);
+                    *value = region + (*value - region) / (1.0 + fraction * rate);
+                }
+            }
+        }
+    }
+}
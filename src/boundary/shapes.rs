@@ -0,0 +1,523 @@
+use crate::util::helper::{clamp, length, max, min, nsgn};
+
+// Signed distance, closest surface point and outward normal of a shape, all in the frame of the body
+pub struct LocalSurface {
+    pub distance: f64,
+    pub closest:  (f64, f64),
+    pub normal:   (f64, f64),
+}
+
+// Builds the surface query for a point a given offset from its closest point on a convex core, such as the
+// spine of a capsule or the inner rectangle of a rounded box, inflated by radius
+fn inflated(x: f64, y: f64, core_x: f64, core_y: f64, radius: f64, fallback: (f64, f64)) -> LocalSurface {
+    let dx = x - core_x;
+    let dy = y - core_y;
+    let r = length(dx, dy);
+    let normal = if r < 1e-9 { fallback } else { (dx / r, dy / r) };
+
+    LocalSurface {
+        distance: r - radius,
+        closest:  (core_x + normal.0 * radius, core_y + normal.1 * radius),
+        normal
+    }
+}
+
+// Ellipse with the given semi-axes, using a fixed number of trig-free iterations for the closest point
+pub fn ellipse(x: f64, y: f64, semi_x: f64, semi_y: f64) -> LocalSurface {
+    let px = x.abs();
+    let py = y.abs();
+
+    let mut tx: f64 = std::f64::consts::FRAC_1_SQRT_2;
+    let mut ty: f64 = std::f64::consts::FRAC_1_SQRT_2;
+
+    for _iteration in 0..4 {
+        let ex = (semi_x * semi_x - semi_y * semi_y) * tx.powi(3) / semi_x;
+        let ey = (semi_y * semi_y - semi_x * semi_x) * ty.powi(3) / semi_y;
+
+        let r = length(semi_x * tx - ex, semi_y * ty - ey);
+        let q = max(length(px - ex, py - ey), 1e-12);
+
+        tx = clamp(((px - ex) * r / q + ex) / semi_x, 0.0, 1.0);
+        ty = clamp(((py - ey) * r / q + ey) / semi_y, 0.0, 1.0);
+
+        let t = max(length(tx, ty), 1e-12);
+        tx /= t;
+        ty /= t;
+    }
+
+    let closest = (nsgn(x) * semi_x * tx, nsgn(y) * semi_y * ty);
+
+    let nx = closest.0 / (semi_x * semi_x);
+    let ny = closest.1 / (semi_y * semi_y);
+    let n = length(nx, ny);
+
+    let inside = (x / semi_x).powi(2) + (y / semi_y).powi(2) < 1.0;
+    let d = length(x - closest.0, y - closest.1);
+
+    LocalSurface {
+        distance: if inside { -d } else { d },
+        closest,
+        normal:   (nx / n, ny / n)
+    }
+}
+
+// Capsule along the x axis with the given overall length and end radius
+pub fn capsule(x: f64, y: f64, overall_length: f64, radius: f64) -> LocalSurface {
+    let half = max(0.5 * overall_length - radius, 0.0);
+
+    inflated(x, y, clamp(x, -half, half), 0.0, radius, (0.0, nsgn(y)))
+}
+
+// Rectangle with the given half extents whose corners are rounded with the given radius
+pub fn rounded_box(x: f64, y: f64, half_x: f64, half_y: f64, radius: f64) -> LocalSurface {
+    let radius = min(radius, min(half_x, half_y));
+    let inner_x = half_x - radius;
+    let inner_y = half_y - radius;
+
+    let qx = x.abs() - inner_x;
+    let qy = y.abs() - inner_y;
+
+    if qx > 0.0 || qy > 0.0 {
+        inflated(x, y, clamp(x, -inner_x, inner_x), clamp(y, -inner_y, inner_y), radius, (nsgn(x), 0.0))
+    } else if qx > qy {
+        LocalSurface {
+            distance: qx - radius,
+            closest:  (nsgn(x) * half_x, y),
+            normal:   (nsgn(x), 0.0)
+        }
+    } else {
+        LocalSurface {
+            distance: qy - radius,
+            closest:  (x, nsgn(y) * half_y),
+            normal:   (0.0, nsgn(y))
+        }
+    }
+}
+
+// Arbitrary simple polygon, convex or concave, given by its vertices in order
+pub fn polygon(x: f64, y: f64, vertices: &[(f64, f64)]) -> LocalSurface {
+    let count = vertices.len();
+
+    // Signed area decides which side of each edge faces outward
+    let mut area = 0.0;
+    for i in 0..count {
+        let (ax, ay) = vertices[i];
+        let (bx, by) = vertices[(i + 1) % count];
+        area += ax * by - bx * ay;
+    }
+
+    let orientation = nsgn(area);
+
+    let mut best = f64::INFINITY;
+    let mut closest = vertices[0];
+    let mut edge_normal = (1.0, 0.0);
+    let mut inside = false;
+
+    for i in 0..count {
+        let (ax, ay) = vertices[i];
+        let (bx, by) = vertices[(i + 1) % count];
+
+        let ex = bx - ax;
+        let ey = by - ay;
+        let edge = ex * ex + ey * ey;
+        let t = if edge > 0.0 { clamp(((x - ax) * ex + (y - ay) * ey) / edge, 0.0, 1.0) } else { 0.0 };
+
+        let cx = ax + t * ex;
+        let cy = ay + t * ey;
+        let d = length(x - cx, y - cy);
+
+        if d < best {
+            best = d;
+            closest = (cx, cy);
+
+            let e = max(edge.sqrt(), 1e-12);
+            edge_normal = (orientation * ey / e, -orientation * ex / e);
+        }
+
+        // Crossing test against a ray along +x
+        if (ay > y) != (by > y) && x < ax + (y - ay) * ex / ey {
+            inside = !inside;
+        }
+    }
+
+    let sign = if inside { -1.0 } else { 1.0 };
+    let normal = if best < 1e-9 {
+        edge_normal
+    } else {
+        (sign * (x - closest.0) / best, sign * (y - closest.1) / best)
+    };
+
+    LocalSurface {
+        distance: sign * best,
+        closest,
+        normal
+    }
+}
+
+// Outline of a NACA 4-digit section such as "2412" with unit chord, leading edge at the origin and the
+// suction side towards -y. Points are cosine spaced and the trailing edge is closed
+pub fn naca_four_digit(code: &str, points: usize) -> Vec<(f64, f64)> {
+    let digits: Vec<f64> = code.chars().filter_map(|c| c.to_digit(10)).map(|d| d as f64).collect();
+
+    if digits.len() != 4 {
+        panic!("NACA 4-digit code {} must contain exactly four digits", code);
+    }
+
+    let camber = digits[0] / 100.0;
+    let position = digits[1] / 10.0;
+    let thickness = (10.0 * digits[2] + digits[3]) / 100.0;
+
+    let section = |s: f64| {
+        let half_thickness = 5.0 * thickness * (0.2969 * s.sqrt() - 0.1260 * s - 0.3516 * s * s + 0.2843 * s.powi(3) - 0.1036 * s.powi(4));
+
+        let (mean, slope) = if camber == 0.0 || position == 0.0 {
+            (0.0, 0.0)
+        } else if s < position {
+            (camber / (position * position) * (2.0 * position * s - s * s), 2.0 * camber / (position * position) * (position - s))
+        } else {
+            let p = 1.0 - position;
+            (camber / (p * p) * (1.0 - 2.0 * position + 2.0 * position * s - s * s), 2.0 * camber / (p * p) * (position - s))
+        };
+
+        let angle = slope.atan();
+        ((s - half_thickness * angle.sin(), mean + half_thickness * angle.cos()),
+         (s + half_thickness * angle.sin(), mean - half_thickness * angle.cos()))
+    };
+
+    let stations: Vec<f64> = (0..=points).map(|i| 0.5 * (1.0 - (std::f64::consts::PI * i as f64 / points as f64).cos())).collect();
+
+    let mut vertices = Vec::new();
+
+    // Upper surface from the trailing edge forward, then the lower surface back, flipping y so camber points up in images
+    for s in stations.iter().rev() {
+        let (upper, _) = section(*s);
+        vertices.push((upper.0, -upper.1));
+    }
+
+    for s in stations.iter().skip(1).take(points - 1) {
+        let (_, lower) = section(*s);
+        vertices.push((lower.0, -lower.1));
+    }
+
+    vertices
+}
+
+// Squared distance transform of one line of samples, where each sample holds the squared distance to the nearest
+// feature found so far. Computes the lower envelope of parabolas rooted at every sample
+fn distance_transform_line(samples: &mut [f64]) {
+    let count = samples.len();
+    let source = samples.to_vec();
+
+    let mut roots = vec![0usize; count];
+    let mut bounds = vec![0.0; count + 1];
+    let mut hull = 0;
+
+    bounds[0] = f64::NEG_INFINITY;
+    bounds[1] = f64::INFINITY;
+
+    let intersection = |q: usize, r: usize| {
+        ((source[q] + (q * q) as f64) - (source[r] + (r * r) as f64)) / (2.0 * q as f64 - 2.0 * r as f64)
+    };
+
+    for q in 1..count {
+        if source[q].is_infinite() {
+            continue;
+        }
+
+        if source[roots[hull]].is_infinite() {
+            roots[hull] = q;
+            continue;
+        }
+
+        let mut s = intersection(q, roots[hull]);
+        while s <= bounds[hull] {
+            hull -= 1;
+            s = intersection(q, roots[hull]);
+        }
+
+        hull += 1;
+        roots[hull] = q;
+        bounds[hull] = s;
+        bounds[hull + 1] = f64::INFINITY;
+    }
+
+    if source[roots[hull]].is_infinite() {
+        return;
+    }
+
+    hull = 0;
+    for (q, sample) in samples.iter_mut().enumerate() {
+        while bounds[hull + 1] < q as f64 {
+            hull += 1;
+        }
+
+        let offset = q as f64 - roots[hull] as f64;
+        *sample = offset * offset + source[roots[hull]];
+    }
+}
+
+// Euclidean distance in pixels from every pixel centre to the nearest pixel centre where feature is set
+fn distance_transform(feature: &[bool], width: usize, height: usize) -> Vec<f64> {
+    let mut squared: Vec<f64> = feature.iter().map(|&set| if set { 0.0 } else { f64::INFINITY }).collect();
+
+    for row in squared.chunks_mut(width) {
+        distance_transform_line(row);
+    }
+
+    let mut column = vec![0.0; height];
+    for x in 0..width {
+        for y in 0..height {
+            column[y] = squared[y * width + x];
+        }
+
+        distance_transform_line(&mut column);
+
+        for y in 0..height {
+            squared[y * width + x] = column[y];
+        }
+    }
+
+    squared.into_iter().map(f64::sqrt).collect()
+}
+
+// Signed distance field sampled at pixel centres of a solid mask, centred on the origin of the body frame
+pub struct DistanceGrid {
+    distances: Vec<f64>,
+    width: usize,
+    height: usize,
+    pixel_size: f64
+}
+
+impl DistanceGrid {
+    // Builds the field from a row major mask where true marks solid pixels. The surface is taken half a pixel
+    // from the centres of the pixels on either side of it
+    pub fn new(mask: &[bool], width: usize, height: usize, pixel_size: f64) -> Self {
+        if mask.len() != width * height {
+            panic!("Mask has {} pixels but {}x{} were given", mask.len(), width, height);
+        }
+
+        let empty: Vec<bool> = mask.iter().map(|&solid| !solid).collect();
+        let outside = distance_transform(mask, width, height);
+        let inside = distance_transform(&empty, width, height);
+
+        let distances = mask.iter().enumerate().map(|(pixel, &solid)| {
+            let distance = if solid { 0.5 - inside[pixel] } else { outside[pixel] - 0.5 };
+
+            // Masks without any solid or empty pixels leave infinite distances behind
+            clamp(distance, -((width + height) as f64), (width + height) as f64) * pixel_size
+        }).collect();
+
+        DistanceGrid {
+            distances,
+            width,
+            height,
+            pixel_size
+        }
+    }
+
+    // Bilinear sample of the field, adding the distance to the image for points beyond the outermost pixel centres
+    fn sample(&self, x: f64, y: f64) -> f64 {
+        let grid_x = x / self.pixel_size + 0.5 * self.width as f64 - 0.5;
+        let grid_y = y / self.pixel_size + 0.5 * self.height as f64 - 0.5;
+
+        let clamped_x = clamp(grid_x, 0.0, (self.width - 1) as f64);
+        let clamped_y = clamp(grid_y, 0.0, (self.height - 1) as f64);
+
+        let column = min(clamped_x.floor() as usize, self.width.saturating_sub(2));
+        let row = min(clamped_y.floor() as usize, self.height.saturating_sub(2));
+        let next_column = min(column + 1, self.width - 1);
+        let next_row = min(row + 1, self.height - 1);
+
+        let fx = clamped_x - column as f64;
+        let fy = clamped_y - row as f64;
+
+        let top = (1.0 - fx) * self.distances[row * self.width + column] + fx * self.distances[row * self.width + next_column];
+        let bottom = (1.0 - fx) * self.distances[next_row * self.width + column] + fx * self.distances[next_row * self.width + next_column];
+
+        (1.0 - fy) * top + fy * bottom + length(grid_x - clamped_x, grid_y - clamped_y) * self.pixel_size
+    }
+
+    pub fn surface(&self, x: f64, y: f64) -> LocalSurface {
+        let gradient = |x: f64, y: f64| {
+            let h = 0.5 * self.pixel_size;
+            let gx = self.sample(x + h, y) - self.sample(x - h, y);
+            let gy = self.sample(x, y + h) - self.sample(x, y - h);
+            let g = length(gx, gy);
+
+            if g < 1e-12 { (1.0, 0.0) } else { (gx / g, gy / g) }
+        };
+
+        let distance = self.sample(x, y);
+        let normal = gradient(x, y);
+
+        // Sampled distances are only approximate, so step down the gradient a few times towards the zero contour
+        let mut closest = (x, y);
+        for _iteration in 0..4 {
+            let step = self.sample(closest.0, closest.1);
+            let (step_x, step_y) = gradient(closest.0, closest.1);
+            closest = (closest.0 - step * step_x, closest.1 - step * step_y);
+        }
+
+        LocalSurface {
+            distance,
+            closest,
+            normal
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!((actual - expected).abs() < tolerance, "{} is not within {} of {}", actual, tolerance, expected);
+    }
+
+    // Checks the distance, and that the closest point lies that far away along the normal
+    fn assert_surface(surface: &LocalSurface, x: f64, y: f64, distance: f64, tolerance: f64) {
+        assert_close(surface.distance, distance, tolerance);
+        assert_close(length(surface.normal.0, surface.normal.1), 1.0, 1e-9);
+        assert_close(surface.closest.0 + surface.distance * surface.normal.0, x, tolerance);
+        assert_close(surface.closest.1 + surface.distance * surface.normal.1, y, tolerance);
+    }
+
+    #[test]
+    fn ellipse_matches_a_circle() {
+        assert_surface(&ellipse(2.0, 0.0, 1.0, 1.0), 2.0, 0.0, 1.0, 1e-9);
+        assert_surface(&ellipse(0.0, -0.25, 1.0, 1.0), 0.0, -0.25, -0.75, 1e-9);
+        assert_surface(&ellipse(1.2, 1.6, 1.0, 1.0), 1.2, 1.6, 1.0, 1e-6);
+    }
+
+    #[test]
+    fn ellipse_matches_a_sampled_outline() {
+        let (semi_x, semi_y) = (2.0, 0.5);
+        let samples = 100000;
+
+        for &(x, y) in &[(3.0, 1.0), (-0.5, 1.5), (1.0, -0.1), (0.0, 0.0), (-2.5, -0.2)] {
+            let nearest = (0..samples).map(|i| {
+                let angle = 2.0 * std::f64::consts::PI * i as f64 / samples as f64;
+                length(x - semi_x * angle.cos(), y - semi_y * angle.sin())
+            }).fold(f64::INFINITY, f64::min);
+
+            let inside = (x / semi_x).powi(2) + (y / semi_y).powi(2) < 1.0;
+            let surface = ellipse(x, y, semi_x, semi_y);
+
+            assert_close(surface.distance, if inside { -nearest } else { nearest }, 1e-3);
+        }
+    }
+
+    #[test]
+    fn capsule_is_a_rounded_segment() {
+        assert_surface(&capsule(0.0, 2.0, 4.0, 1.0), 0.0, 2.0, 1.0, 1e-9);
+        assert_surface(&capsule(3.0, 0.0, 4.0, 1.0), 3.0, 0.0, 1.0, 1e-9);
+        assert_surface(&capsule(-1.0 - 0.6, 0.8, 4.0, 1.0), -1.6, 0.8, 0.0, 1e-9);
+        assert_surface(&capsule(0.5, -0.25, 4.0, 1.0), 0.5, -0.25, -0.75, 1e-9);
+    }
+
+    #[test]
+    fn rounded_box_has_flat_sides_and_round_corners() {
+        assert_surface(&rounded_box(3.0, 0.0, 2.0, 1.0, 0.5), 3.0, 0.0, 1.0, 1e-9);
+        assert_surface(&rounded_box(0.0, -1.5, 2.0, 1.0, 0.5), 0.0, -1.5, 0.5, 1e-9);
+        assert_surface(&rounded_box(0.5, 0.25, 2.0, 1.0, 0.5), 0.5, 0.25, -0.75, 1e-9);
+        assert_surface(&rounded_box(3.0, 2.0, 2.0, 1.0, 0.5), 3.0, 2.0, length(1.5, 1.5) - 0.5, 1e-9);
+    }
+
+    #[test]
+    fn polygon_distance_does_not_depend_on_winding() {
+        let square = vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let reversed: Vec<(f64, f64)> = square.iter().rev().cloned().collect();
+
+        for vertices in &[square, reversed] {
+            assert_surface(&polygon(2.0, 0.5, vertices), 2.0, 0.5, 1.0, 1e-9);
+            assert_surface(&polygon(0.5, 0.25, vertices), 0.5, 0.25, -0.25, 1e-9);
+            assert_surface(&polygon(-3.0, -4.0, vertices), -3.0, -4.0, 5.0, 1e-9);
+        }
+    }
+
+    #[test]
+    fn polygon_handles_concave_outlines() {
+        // L shape whose notch covers the upper right quarter of the square
+        let vertices = vec![(0.0, 0.0), (2.0, 0.0), (2.0, 1.0), (1.0, 1.0), (1.0, 2.0), (0.0, 2.0)];
+
+        assert_surface(&polygon(1.5, 1.5, &vertices), 1.5, 1.5, 0.5, 1e-9);
+        assert_surface(&polygon(0.5, 1.5, &vertices), 0.5, 1.5, -0.5, 1e-9);
+    }
+
+    #[test]
+    fn symmetric_naca_section_has_its_thickness() {
+        let points = 100;
+        let vertices = naca_four_digit("0012", points);
+
+        assert_eq!(vertices.len(), 2 * points);
+        assert_close(vertices[0].0, 1.0, 1e-12);
+        assert_close(vertices[points].0, 0.0, 1e-12);
+
+        let upper = vertices.iter().map(|vertex| vertex.1).fold(f64::INFINITY, f64::min);
+        let lower = vertices.iter().map(|vertex| vertex.1).fold(f64::NEG_INFINITY, f64::max);
+
+        assert_close(upper, -0.06, 1e-3);
+        assert_close(lower, 0.06, 1e-3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn naca_code_needs_four_digits() {
+        naca_four_digit("241", 50);
+    }
+
+    #[test]
+    fn distance_transform_is_euclidean() {
+        let (width, height) = (5, 4);
+        let mut feature = vec![false; width * height];
+        feature[width + 1] = true;
+        feature[3 * width + 4] = true;
+
+        let distances = distance_transform(&feature, width, height);
+
+        for row in 0..height {
+            for column in 0..width {
+                let first = length(column as f64 - 1.0, row as f64 - 1.0);
+                let second = length(column as f64 - 4.0, row as f64 - 3.0);
+                assert_close(distances[row * width + column], first.min(second), 1e-12);
+            }
+        }
+    }
+
+    // Twelve pixels a side of a tenth each, with a solid block of four by four in the middle
+    fn block() -> DistanceGrid {
+        let mask: Vec<bool> = (0..144).map(|pixel| (4..8).contains(&(pixel / 12)) && (4..8).contains(&(pixel % 12))).collect();
+        DistanceGrid::new(&mask, 12, 12, 0.1)
+    }
+
+    #[test]
+    fn bitmap_distance_is_signed_from_the_pixel_edges() {
+        let grid = block();
+
+        assert_close(grid.surface(0.45, -0.05).distance, 0.25, 1e-9);
+        assert_close(grid.surface(-0.05, 0.35).distance, 0.15, 1e-9);
+        assert_close(grid.surface(-0.05, -0.05).distance, -0.15, 1e-9);
+        assert_close(grid.surface(0.45, 0.45).distance, length(0.25, 0.25), 0.03);
+    }
+
+    #[test]
+    fn bitmap_distance_grows_beyond_the_image() {
+        let surface = block().surface(1.0, -0.05);
+
+        assert_close(surface.distance, 0.8, 1e-9);
+        assert_close(surface.normal.0, 1.0, 1e-9);
+        assert_close(surface.closest.0, 0.2, 1e-6);
+    }
+
+    #[test]
+    fn empty_bitmap_stays_finite() {
+        let grid = DistanceGrid::new(&[false; 16], 4, 4, 0.1);
+        assert!(grid.surface(0.0, 0.0).distance.is_finite());
+    }
+
+    #[test]
+    #[should_panic]
+    fn bitmap_mask_must_match_its_size() {
+        DistanceGrid::new(&[true; 10], 4, 4, 0.1);
+    }
+}
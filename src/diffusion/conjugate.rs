@@ -0,0 +1,239 @@
+use crate::boundary::{SolidBody, ThermalCondition};
+use crate::linear_solvers::LinearSolver;
+use crate::util::fluid_quantity::FluidQuantity;
+use crate::util::helper::{clamp, length, max};
+use crate::util::sparse::Sparse;
+
+// Conductivity and heat capacity of a cell relative to the fluid, or None where the cell takes no part in the solve
+fn cell_properties(temperature: &FluidQuantity, bodies: &[SolidBody], element: usize, diffusivity: f64) -> Option<(f64, f64)> {
+    if temperature.cell[element] == 0 {
+        return Some((diffusivity, temperature.volume[element]));
+    }
+
+    match bodies[temperature.body[element] as usize].thermal() {
+        ThermalCondition::Conducting(solid_diffusivity, ratio, _) => Some((ratio * diffusivity, ratio * diffusivity / solid_diffusivity)),
+        _ => None
+    }
+}
+
+// Fraction of the way from the centre of a fluid cell to the centre of a neighbouring solid cell at which the surface of
+// the solid crosses, kept away from either end so that neither side of the face vanishes
+fn fluid_side(temperature: &FluidQuantity, bodies: &[SolidBody], fluid: usize, solid: usize) -> f64 {
+    let cell_size = temperature.cell_size;
+    let body = &bodies[temperature.body[solid] as usize];
+    let centre = |element: usize| (((element % temperature.columns) as f64 + 0.5) * cell_size, ((element / temperature.columns) as f64 + 0.5) * cell_size);
+
+    let (fluid_x, fluid_y) = centre(fluid);
+    let (solid_x, solid_y) = centre(solid);
+    let outside = max(body.distance(fluid_x, fluid_y), 0.0);
+    let inside = max(-body.distance(solid_x, solid_y), 0.0);
+
+    clamp(outside / max(outside + inside, 1e-12), 0.05, 0.95)
+}
+
+// Conductance of the face between two cells taking part in the solve. Between fluid and a conducting body the face is
+// split where the surface crosses and the two sides are added in series
+fn face_conductance(temperature: &FluidQuantity, bodies: &[SolidBody], first: usize, second: usize, fraction: f64, conductivities: (f64, f64)) -> f64 {
+    let (first_fluid, second_fluid) = (temperature.cell[first] == 0, temperature.cell[second] == 0);
+
+    let split = match (first_fluid, second_fluid) {
+        (true, true) => return conductivities.0 * fraction,
+        (true, false) => fluid_side(temperature, bodies, first, second),
+        (false, true) => 1.0 - fluid_side(temperature, bodies, second, first),
+        (false, false) => 0.5
+    };
+
+    1.0 / (split / conductivities.0 + (1.0 - split) / conductivities.1)
+}
+
+// Geometry and material of a conjugate heat transfer solve. The face fractions come from the two velocity components,
+// and the diffusivity is that of the fluid
+pub struct ConjugateProblem<'a> {
+    pub x_faces: &'a FluidQuantity,
+    pub y_faces: &'a FluidQuantity,
+    pub bodies: &'a [SolidBody],
+    pub diffusivity: f64,
+    pub timestep: f64
+}
+
+// Diffuses temperature through the fluid and every conducting body in one implicit solve, coupling them through the
+// cut-cell interface. Bodies at a fixed temperature or heat flux act on the fluid cells they cut through the embedded
+// surface of each cell, whose area and distance come from the face fractions and the body distance field. The heat
+// flow into the fluid, surface area and mean surface temperature of each body are written to heat
+pub fn diffuse_conjugate(temperature: &mut FluidQuantity, solid_temperature: &mut [f64], problem: &ConjugateProblem, heat: &mut [(f64, f64, f64)], linear_solver: &mut LinearSolver, iterations: usize) {
+    let ConjugateProblem { x_faces, y_faces, bodies, diffusivity, timestep } = *problem;
+    let rows = temperature.rows;
+    let columns = temperature.columns;
+    let cell_size = temperature.cell_size;
+    let size = rows * columns;
+    let scale = timestep / (cell_size * cell_size);
+
+    let mut a = Sparse::new(size);
+    a.periodic_x = temperature.periodic_x;
+    a.periodic_y = temperature.periodic_y;
+
+    let mut rhs = vec![0.0; size];
+    let mut active = vec![1u8; size];
+    let mut result = temperature.src.clone();
+    let properties: Vec<Option<(f64, f64)>> = (0..size).map(|element| cell_properties(temperature, bodies, element, diffusivity)).collect();
+
+    // Conducting bodies keep their own temperature in the solid cells. Cells joining a body start from its initial value
+    for element in 0..size {
+        if properties[element].is_some() && temperature.cell[element] != 0 {
+            if solid_temperature[element].is_nan() {
+                if let ThermalCondition::Conducting(_, _, initial) = bodies[temperature.body[element] as usize].thermal() {
+                    solid_temperature[element] = *initial;
+                }
+            }
+            result[element] = solid_temperature[element];
+        } else {
+            solid_temperature[element] = f64::NAN;
+        }
+    }
+
+    // Surface area, heat flow and summed surface temperature of each body, from the embedded surface of every cut cell
+    let mut surface = vec![(0.0, 0.0, 0.0); bodies.len()];
+    let mut embedded = Vec::new();
+
+    for row in 0..rows {
+        for column in 0..columns {
+            let element = row * columns + column;
+            let (conductivity, capacity) = match properties[element] {
+                Some(properties) => properties,
+                None => continue
+            };
+
+            active[element] = 0;
+            a.diagonals[element] += capacity;
+            rhs[element] += capacity * result[element];
+
+            let right = if column < columns - 1 { Some(element + 1) } else if a.periodic_x { Some(element + 1 - columns) } else { None };
+            let below = if row < rows - 1 { Some(element + columns) } else if a.periodic_y { Some(element - (rows - 1) * columns) } else { None };
+
+            if let Some((neighbour, (other, _))) = right.and_then(|neighbour| properties[neighbour].map(|properties| (neighbour, properties))) {
+                let factor = scale * face_conductance(temperature, bodies, element, neighbour, x_faces.volume_at(row, column + 1), (conductivity, other));
+                a.diagonals[element] += factor;
+                a.diagonals[neighbour] += factor;
+                a.plus_x[element] = -factor;
+            }
+
+            if let Some((neighbour, (other, _))) = below.and_then(|neighbour| properties[neighbour].map(|properties| (neighbour, properties))) {
+                let factor = scale * face_conductance(temperature, bodies, element, neighbour, y_faces.volume_at(row + 1, column), (conductivity, other));
+                a.diagonals[element] += factor;
+                a.diagonals[neighbour] += factor;
+                a.plus_y[element] = -factor;
+            }
+
+            if temperature.cell[element] != 0 {
+                continue;
+            }
+
+            let area_x = x_faces.volume_at(row, column + 1) - x_faces.volume_at(row, column);
+            let area_y = y_faces.volume_at(row + 1, column) - y_faces.volume_at(row, column);
+            let area = length(area_x, area_y);
+
+            if area == 0.0 {
+                continue;
+            }
+
+            let index = temperature.body[element] as usize;
+            let x = (column as f64 + 0.5) * cell_size;
+            let y = (row as f64 + 0.5) * cell_size;
+            let distance = max(bodies[index].distance(x, y), 0.25 * cell_size) / cell_size;
+
+            surface[index].0 += area * cell_size;
+
+            match bodies[index].thermal() {
+                ThermalCondition::FixedTemperature(wall) => {
+                    a.diagonals[element] += scale * diffusivity * area / distance;
+                    rhs[element] += scale * diffusivity * area / distance * wall;
+                }
+                ThermalCondition::HeatFlux(flux) => {
+                    rhs[element] += timestep * flux * area / cell_size;
+                }
+                _ => {}
+            }
+
+            embedded.push((element, index, area, distance));
+        }
+    }
+
+    let mut auxiliary = vec![0.0; size];
+    let mut search = vec![0.0; size];
+    let mut preconditioner = vec![0.0; size];
+
+    linear_solver.solve(&mut result, &mut rhs, &mut auxiliary, &mut search, &mut preconditioner, &a, &active, rows, columns, iterations);
+
+    for element in 0..size {
+        if temperature.cell[element] == 0 {
+            temperature.src[element] = result[element];
+        } else if active[element] == 0 {
+            solid_temperature[element] = result[element];
+            temperature.src[element] = result[element];
+        }
+    }
+
+    for &(element, index, area, distance) in &embedded {
+        let fluid = result[element];
+
+        match bodies[index].thermal() {
+            ThermalCondition::FixedTemperature(wall) => {
+                surface[index].1 += diffusivity * area / distance * (wall - fluid);
+                surface[index].2 += wall * area * cell_size;
+            }
+            ThermalCondition::HeatFlux(flux) => {
+                surface[index].1 += flux * area * cell_size;
+                surface[index].2 += (fluid + flux * distance * cell_size / diffusivity) * area * cell_size;
+            }
+            _ => {}
+        }
+    }
+
+    // Conducting bodies pass their heat through the faces shared with fluid cells, and the surface temperature on each
+    // face is the value where the two sides in series meet
+    let mut interfaces = vec![(0.0, 0.0); bodies.len()];
+
+    for row in 0..rows {
+        for column in 0..columns {
+            let element = row * columns + column;
+
+            if temperature.cell[element] == 0 || active[element] != 0 {
+                continue;
+            }
+
+            let index = temperature.body[element] as usize;
+            let neighbours = [
+                (column > 0, element.wrapping_sub(1), x_faces.volume_at(row, column)),
+                (column < columns - 1, element + 1, x_faces.volume_at(row, column + 1)),
+                (row > 0, element.wrapping_sub(columns), y_faces.volume_at(row, column)),
+                (row < rows - 1, element + columns, y_faces.volume_at(row + 1, column))
+            ];
+
+            for &(inside, neighbour, fraction) in &neighbours {
+                if !inside || temperature.cell[neighbour] != 0 {
+                    continue;
+                }
+
+                let (solid, fluid) = (properties[element].unwrap().0, diffusivity);
+                let conductance = face_conductance(temperature, bodies, neighbour, element, fraction, (fluid, solid));
+                surface[index].1 += conductance * (result[element] - result[neighbour]);
+
+                // Flux continuity gives the interface value from the resistance of the fluid side alone
+                let split = fluid_side(temperature, bodies, neighbour, element);
+                interfaces[index].0 += result[neighbour] + conductance * split / fluid * (result[element] - result[neighbour]);
+                interfaces[index].1 += 1.0;
+            }
+        }
+    }
+
+    for (index, total) in heat.iter_mut().enumerate() {
+        let (area, flow, temperature_sum) = surface[index];
+        let mean = match bodies[index].thermal() {
+            ThermalCondition::Conducting(_, _, _) if interfaces[index].1 > 0.0 => interfaces[index].0 / interfaces[index].1,
+            _ if area > 0.0 => temperature_sum / area,
+            _ => 0.0
+        };
+
+        *total = (flow, area, mean);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::linear_solvers::LinearSolver;
+use crate::util::fluid_quantity::FluidQuantity;
+use crate::util::sparse::Sparse;
+
+mod conjugate;
+
+pub use self::conjugate::{diffuse_conjugate, ConjugateProblem};
+
+// Assembles the implicit diffusion system (I - scale * L) q' = q over the free nodes of a quantity.
+// Fixed nodes contribute their current value as a Dirichlet condition one cell away, while the domain
+// edges ordered [x = 0, x = max, y = 0, y = max] add wall_factor * scale to the diagonal of nodes next to them.
+// Periodic seams are coupled through the current value of the wrapped neighbour, since face centred grids
+// store the seam twice and cannot be expressed in the wrapped stencil of Sparse
+pub fn build_diffusion_matrix(a: &mut Sparse, rhs: &mut [f64], fixed: &[u8], quantity: &FluidQuantity, scale: f64, wall_factor: [f64; 4]) {
+    let rows = quantity.rows;
+    let columns = quantity.columns;
+
+    *a = Sparse::new(rows * columns);
+
+    for row in 0..rows {
+        for column in 0..columns {
+            let element = row * columns + column;
+
+            if fixed[element] != 0 {
+                rhs[element] = 0.0;
+                continue;
+            }
+
+            a.diagonals[element] += 1.0;
+            rhs[element] = quantity.src[element];
+
+            if column == 0 && quantity.periodic_x {
+                a.diagonals[element] += scale;
+                rhs[element] += scale * quantity.wrapped_at(row as isize, -1);
+            } else if column == 0 {
+                a.diagonals[element] += wall_factor[0] * scale;
+            } else if fixed[element - 1] != 0 {
+                a.diagonals[element] += scale;
+                rhs[element] += scale * quantity.src[element - 1];
+            }
+
+            if column == columns - 1 && quantity.periodic_x {
+                a.diagonals[element] += scale;
+                rhs[element] += scale * quantity.wrapped_at(row as isize, columns as isize);
+            } else if column == columns - 1 {
+                a.diagonals[element] += wall_factor[1] * scale;
+            } else if fixed[element + 1] != 0 {
+                a.diagonals[element] += scale;
+                rhs[element] += scale * quantity.src[element + 1];
+            } else {
+                a.diagonals[element] += scale;
+                a.diagonals[element + 1] += scale;
+                a.plus_x[element] = -scale;
+            }
+
+            if row == 0 && quantity.periodic_y {
+                a.diagonals[element] += scale;
+                rhs[element] += scale * quantity.wrapped_at(-1, column as isize);
+            } else if row == 0 {
+                a.diagonals[element] += wall_factor[2] * scale;
+            } else if fixed[element - columns] != 0 {
+                a.diagonals[element] += scale;
+                rhs[element] += scale * quantity.src[element - columns];
+            }
+
+            if row == rows - 1 && quantity.periodic_y {
+                a.diagonals[element] += scale;
+                rhs[element] += scale * quantity.wrapped_at(rows as isize, column as isize);
+            } else if row == rows - 1 {
+                a.diagonals[element] += wall_factor[3] * scale;
+            } else if fixed[element + columns] != 0 {
+                a.diagonals[element] += scale;
+                rhs[element] += scale * quantity.src[element + columns];
+            } else {
+                a.diagonals[element] += scale;
+                a.diagonals[element + columns] += scale;
+                a.plus_y[element] = -scale;
+            }
+        }
+    }
+}
+
+// Performs one implicit diffusion step on the free nodes of a quantity using the given linear solver
+pub fn diffuse(quantity: &mut FluidQuantity, fixed: &[u8], scale: f64, wall_factor: [f64; 4], linear_solver: &mut LinearSolver, iterations: usize) {
+    let size = quantity.rows * quantity.columns;
+
+    let mut a = Sparse::new(size);
+    let mut rhs = vec![0.0; size];
+    let mut auxiliary = vec![0.0; size];
+    let mut search = vec![0.0; size];
+    let mut preconditioner = vec![0.0; size];
+    let mut result = quantity.src.clone();
+
+    build_diffusion_matrix(&mut a, &mut rhs, fixed, quantity, scale, wall_factor);
+    linear_solver.solve(&mut result, &mut rhs, &mut auxiliary, &mut search, &mut preconditioner, &a, fixed, quantity.rows, quantity.columns, iterations);
+
+    for element in 0..size {
+        if fixed[element] == 0 {
+            quantity.src[element] = result[element];
+        }
+    }
+}
+
+pub enum Diffusion {
+    Explicit,
+    Implicit
+}
+
+// Assembles (V - scale * L) q' = V q for a cell centred quantity. Each face is weighted by its open fraction,
+// so partially solid faces pass proportionally less flux while solids and non-periodic domain edges pass none
+pub fn build_scalar_diffusion_matrix(a: &mut Sparse, rhs: &mut [f64], quantity: &FluidQuantity, x_faces: &FluidQuantity, y_faces: &FluidQuantity, scale: f64) {
+    let rows = quantity.rows;
+    let columns = quantity.columns;
+
+    *a = Sparse::new(rows * columns);
+    a.periodic_x = quantity.periodic_x;
+    a.periodic_y = quantity.periodic_y;
+
+    for row in 0..rows {
+        for column in 0..columns {
+            let element = row * columns + column;
+
+            if quantity.cell[element] != 0 {
+                rhs[element] = 0.0;
+                continue;
+            }
+
+            a.diagonals[element] += quantity.volume[element];
+            rhs[element] = quantity.volume[element] * quantity.src[element];
+
+            let right = if column < columns - 1 { Some(element + 1) } else if a.periodic_x { Some(element + 1 - columns) } else { None };
+            let below = if row < rows - 1 { Some(element + columns) } else if a.periodic_y { Some(element - (rows - 1) * columns) } else { None };
+
+            if let Some(neighbour) = right.filter(|&neighbour| quantity.cell[neighbour] == 0) {
+                let factor = scale * x_faces.volume_at(row, column + 1);
+                a.diagonals[element] += factor;
+                a.diagonals[neighbour] += factor;
+                a.plus_x[element] = -factor;
+            }
+
+            if let Some(neighbour) = below.filter(|&neighbour| quantity.cell[neighbour] == 0) {
+                let factor = scale * y_faces.volume_at(row + 1, column);
+                a.diagonals[element] += factor;
+                a.diagonals[neighbour] += factor;
+                a.plus_y[element] = -factor;
+            }
+        }
+    }
+}
+
+impl Diffusion {
+    // Diffuses a cell centred quantity through the open faces between fluid cells
+    pub fn diffuse_scalar(&self, quantity: &mut FluidQuantity, x_faces: &FluidQuantity, y_faces: &FluidQuantity, scale: f64, linear_solver: &mut LinearSolver, iterations: usize) {
+        let size = quantity.rows * quantity.columns;
+        let mut a = Sparse::new(size);
+        let mut rhs = vec![0.0; size];
+
+        build_scalar_diffusion_matrix(&mut a, &mut rhs, quantity, x_faces, y_faces, scale);
+
+        match self {
+            Diffusion::Explicit => {
+                // Cut cells down to a hundredth of a cell would need hundreds of substeps, so the explicit update
+                // treats cells smaller than a quarter as that size. This keeps them stable at the cost of holding
+                // their content slightly less exactly
+                let smallest = 0.25;
+
+                // Substep so that no cell exchanges more than half of its content with its neighbours in one substep
+                let mut stiffness: f64 = 0.0;
+                for element in 0..size {
+                    if quantity.cell[element] == 0 {
+                        stiffness = stiffness.max((a.diagonals[element] - quantity.volume[element]) / quantity.volume[element].max(smallest));
+                    }
+                }
+
+                let substeps = (2.0 * stiffness).ceil().max(1.0) as usize;
+
+                for _substep in 0..substeps {
+                    for element in 0..size {
+                        if quantity.cell[element] != 0 {
+                            continue;
+                        }
+
+                        let row = element / quantity.columns;
+                        let column = element % quantity.columns;
+                        let value = quantity.src[element];
+
+                        // The diagonal holds the volume plus the sum of the face weights to each neighbour
+                        let flux = -a.off_diagonal_product(&quantity.src, row, column, quantity.rows, quantity.columns) - (a.diagonals[element] - quantity.volume[element]) * value;
+
+                        quantity.dst[element] = value + flux / (quantity.volume[element].max(smallest) * substeps as f64);
+                    }
+
+                    for element in 0..size {
+                        if quantity.cell[element] == 0 {
+                            quantity.src[element] = quantity.dst[element];
+                        }
+                    }
+                }
+            }
+            Diffusion::Implicit => {
+                let mut auxiliary = vec![0.0; size];
+                let mut search = vec![0.0; size];
+                let mut preconditioner = vec![0.0; size];
+                let mut result = quantity.src.clone();
+
+                linear_solver.solve(&mut result, &mut rhs, &mut auxiliary, &mut search, &mut preconditioner, &a, &quantity.cell, quantity.rows, quantity.columns, iterations);
+
+                for (element, value) in result.into_iter().enumerate() {
+                    if quantity.cell[element] == 0 {
+                        quantity.src[element] = value;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,61 +1,110 @@
 use crate::linear_solvers::LinearSolver;
 use crate::integration::Integration;
 use crate::advection::Advection;
-use crate::util::fluid_quantity::FluidQuantity;
+use crate::diffusion::{diffuse, diffuse_conjugate, ConjugateProblem, Diffusion};
+use crate::forces::BodyForce;
+use crate::immersed::Filament;
+use crate::interface::{crossing, LevelSet, VolumeFraction};
+use crate::util::fluid_quantity::{FluidQuantity, NO_BODY};
 use crate::interpolation::Interpolation;
-use crate::boundary::SolidBody;
-use crate::util::helper::{max, min};
+use crate::boundary::{resolve_collisions, DomainBoundary, PorousRegion, SolidBody, ThermalCondition, WallModel};
+use crate::util::helper::{clamp, length, max, min};
 use crate::util::sparse::Sparse;
+use crate::util::scalar_field::ScalarField;
 
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 pub struct FluidSolver {
-    pub u_velocity:     FluidQuantity,
-    pub v_velocity:     FluidQuantity,
-    pub density:        FluidQuantity,
-    pub rows:           usize,
-    pub columns:        usize,
-    pressure:           Vec<f64>,
-    residual:           Vec<f64>,
-    auxiliary:          Vec<f64>,
-    search:             Vec<f64>,
-    preconditioner:     Vec<f64>,
-    a:                  Sparse,
-    iterations:         usize,
-    timestep:           f64,
-    cell_size:          f64,
-    fluid_density:      f64,
-    linear_solver:      LinearSolver,
-    integration:        Integration,
-    interpolation:      Interpolation,
-    advection:          Advection,
-    bodies:             Vec<SolidBody>
+    pub u_velocity:       FluidQuantity,
+    pub v_velocity:       FluidQuantity,
+    pub density:          FluidQuantity,
+    pub temperature:      FluidQuantity,
+    pub scalars:          Vec<ScalarField>,
+    pub rows:             usize,
+    pub columns:          usize,
+    pressure:             Vec<f64>,
+    pressure_cells:       Vec<u8>,
+    residual:             Vec<f64>,
+    auxiliary:            Vec<f64>,
+    search:               Vec<f64>,
+    preconditioner:       Vec<f64>,
+    a:                    Sparse,
+    iterations:           usize,
+    extrapolation_layers: usize,
+    timestep:             f64,
+    time:                 f64,
+    cell_size:            f64,
+    fluid_density:        f64,
+    viscosity:            f64,
+    thermal_diffusivity:  f64,
+    linear_solver:        LinearSolver,
+    integration:          Integration,
+    interpolation:        Interpolation,
+    advection:            Advection,
+    diffusion:            Diffusion,
+    forces:               Vec<BodyForce>,
+    filaments:            Vec<Filament>,
+    porous_regions:       Vec<PorousRegion>,
+    liquid:               Option<LevelSet>,
+    volume_fraction:      Option<VolumeFraction>,
+    surface_tension:      f64,
+    gas_density:          Option<f64>,
+    cell_density:         Vec<f64>,
+    boundary:             DomainBoundary,
+    bodies:               Vec<SolidBody>,
+    body_forces:          Vec<(f64, f64, f64)>,
+    solid_temperature:    Vec<f64>,
+    heat_transfer:        Vec<(f64, f64, f64)>,
+    timings:              Vec<(&'static str, Duration)>,
+    restitution:          f64
 }
 
 impl FluidSolver {
     // Creates a new FluidSolver. x_velocity has one more column, while y_velocity has 1 more row
     pub fn new(rows: usize, columns: usize, timestep: f64, cell_size: f64, fluid_density: f64, bodies: Vec<SolidBody>) -> FluidSolver {
         FluidSolver {
-            u_velocity:     FluidQuantity::new(rows, columns + 1, 0.0, 0.5, cell_size),
-            v_velocity:     FluidQuantity::new(rows + 1, columns, 0.5, 0.0, cell_size),
-            density:        FluidQuantity::new(rows, columns, 0.5, 0.5, cell_size),
+            u_velocity:           FluidQuantity::new(rows, columns + 1, 0.0, 0.5, cell_size),
+            v_velocity:           FluidQuantity::new(rows + 1, columns, 0.5, 0.0, cell_size),
+            density:              FluidQuantity::new(rows, columns, 0.5, 0.5, cell_size),
+            temperature:          FluidQuantity::new(rows, columns, 0.5, 0.5, cell_size),
+            scalars:              Vec::new(),
             rows,
             columns,
-            pressure:       vec![0.0; rows * columns],
-            residual:       vec![0.0; rows * columns],
-            auxiliary:      vec![0.0; rows * columns],
-            search:         vec![0.0; rows * columns],
-            preconditioner: vec![0.0; rows * columns],
-            a:              Sparse::new(rows * columns),
-            iterations:     600,
+            pressure:             vec![0.0; rows * columns],
+            pressure_cells:       vec![0u8; rows * columns],
+            residual:             vec![0.0; rows * columns],
+            auxiliary:            vec![0.0; rows * columns],
+            search:               vec![0.0; rows * columns],
+            preconditioner:       vec![0.0; rows * columns],
+            a:                    Sparse::new(rows * columns),
+            iterations:           600,
+            extrapolation_layers: usize::MAX,
             timestep,
+            time:                 0.0,
             cell_size,
             fluid_density,
-            linear_solver:  LinearSolver::GaussSiedel,
-            integration:    Integration::BogackiShampine,
-            interpolation:  Interpolation::BiLinear,
-            advection:      Advection::SemiLagrangian,
-            bodies
+            viscosity:            0.0,
+            thermal_diffusivity:  0.0,
+            linear_solver:        LinearSolver::GaussSiedel,
+            integration:          Integration::BogackiShampine,
+            interpolation:        Interpolation::BiLinear,
+            advection:            Advection::SemiLagrangian,
+            diffusion:            Diffusion::Implicit,
+            forces:               Vec::new(),
+            filaments:            Vec::new(),
+            porous_regions:       Vec::new(),
+            liquid:               None,
+            volume_fraction:      None,
+            surface_tension:      0.0,
+            gas_density:          None,
+            cell_density:         vec![fluid_density; rows * columns],
+            boundary:             DomainBoundary::closed(),
+            body_forces:          vec![(0.0, 0.0, 0.0); bodies.len()],
+            solid_temperature:    vec![f64::NAN; rows * columns],
+            heat_transfer:        vec![(0.0, 0.0, 0.0); bodies.len()],
+            timings:              Vec::new(),
+            bodies,
+            restitution:          0.5
         }
     }
 
@@ -83,7 +132,267 @@ impl FluidSolver {
         self
     }
 
-    // Sets boundaries of simulation by setting xy velocities at boundaries to 0
+    // Sets the method used to diffuse temperature and registered scalars
+    pub fn diffusion(mut self, f: Diffusion) -> Self {
+        self.diffusion = f;
+        self
+    }
+
+    // Sets the conditions applied on each edge of the domain
+    pub fn domain_boundary(mut self, f: DomainBoundary) -> Self {
+        self.boundary = f;
+        self.apply_periodicity();
+        self
+    }
+
+    // Registers an external body force, integrated into the velocity field before each projection. Gridded forces must
+    // hold one sample per cell in both grids, otherwise this panics
+    pub fn add_body_force(mut self, force: BodyForce) -> Self {
+        if let BodyForce::Grid(x_grid, y_grid) = &force {
+            if x_grid.len() != self.rows * self.columns || y_grid.len() != self.rows * self.columns {
+                panic!("Gridded body forces need {} x {} samples in each grid", self.rows, self.columns);
+            }
+        }
+
+        self.forces.push(force);
+        self
+    }
+
+    // Adds a deformable filament or membrane coupled to the flow through the immersed boundary method
+    pub fn add_filament(mut self, filament: Filament) -> Self {
+        self.filaments.push(filament);
+        self
+    }
+
+    // Adds a porous region which slows the flow through it by Darcy drag rather than blocking it. The drag scales with
+    // the viscosity over the permeability, so the viscosity has to be set first and this panics in an inviscid fluid
+    pub fn add_porous_region(mut self, region: PorousRegion) -> Self {
+        if self.viscosity <= 0.0 {
+            panic!("Porous regions need a positive viscosity, set it before adding them");
+        }
+
+        self.porous_regions.push(region);
+        self
+    }
+
+    // Sets how many cells into each body fluid values are extrapolated after the projection. Every solid cell is filled
+    // by default, while a few layers are enough for interpolation and cheaper around large bodies
+    pub fn extrapolation_layers(mut self, layers: usize) -> Self {
+        self.extrapolation_layers = layers;
+        self
+    }
+
+    // Simulates a liquid with a free surface in place of a fluid filling the domain. The pressure is only solved in the
+    // liquid, with zero pressure on the surface, and velocities are extrapolated into the air to carry the surface
+    pub fn liquid(mut self, level_set: LevelSet) -> Self {
+        self.liquid = Some(level_set);
+        self.apply_periodicity();
+        self
+    }
+
+    // Simulates two immiscible fluids in place of a liquid with a free surface. The fluid inside the level set has the
+    // fluid density and the one around it the given density, such as water and air. The pressure is solved in both,
+    // weighting each face by the inverse of the density averaged from the cells either side, which keeps density
+    // ratios in the thousands stable. Both fluids share the kinematic viscosity. The second density must be positive,
+    // otherwise this panics
+    pub fn two_phase(mut self, level_set: LevelSet, second_density: f64) -> Self {
+        if second_density <= 0.0 {
+            panic!("The second fluid needs a positive density, got {}", second_density);
+        }
+
+        self.gas_density = Some(second_density);
+        self.liquid(level_set)
+    }
+
+    // Simulates a liquid with a free surface tracked by volume fractions in place of a level set, which keeps the
+    // volume of liquid exact. A level set is rebuilt from the fractions every step for the geometry of the surface
+    pub fn liquid_fraction(mut self, fraction: VolumeFraction) -> Self {
+        let level_set = fraction.level_set();
+        self.volume_fraction = Some(fraction);
+        self.liquid(level_set)
+    }
+
+    // Simulates two immiscible fluids as in two_phase, with the first fluid tracked by volume fractions
+    pub fn two_phase_fraction(mut self, fraction: VolumeFraction, second_density: f64) -> Self {
+        let level_set = fraction.level_set();
+        self.volume_fraction = Some(fraction);
+        self.two_phase(level_set, second_density)
+    }
+
+    // Sets the surface tension coefficient of the liquid surface, in force per unit length. Each update is split into
+    // substeps short enough to resolve the capillary waves on the grid. There has to be a surface, so the liquid must
+    // be set first, otherwise this panics
+    pub fn surface_tension(mut self, coefficient: f64) -> Self {
+        if self.liquid.is_none() {
+            panic!("Surface tension needs a liquid, set one with liquid, two_phase or their fraction forms first");
+        }
+
+        self.surface_tension = coefficient;
+        self
+    }
+
+    // Sets the fraction of the approach speed kept when free bodies hit each other or the domain walls
+    pub fn restitution(mut self, restitution: f64) -> Self {
+        self.restitution = restitution;
+        self
+    }
+
+    // Sets the kinematic viscosity, solved implicitly each step. Zero leaves the fluid inviscid, which porous regions
+    // do not allow
+    pub fn viscosity(mut self, viscosity: f64) -> Self {
+        if viscosity <= 0.0 && !self.porous_regions.is_empty() {
+            panic!("Porous regions need a positive viscosity");
+        }
+
+        self.viscosity = viscosity;
+        self
+    }
+
+    // Enables Boussinesq buoyancy, registering it as a body force and starting the temperature field at ambient.
+    // Gravity acts along +y, smoke density adds weight and heat above ambient adds lift
+    pub fn buoyancy(mut self, gravity: f64, density_factor: f64, expansion_factor: f64, ambient_temperature: f64) -> Self {
+        for value in &mut self.temperature.src {
+            *value = ambient_temperature;
+        }
+
+        self.add_body_force(BodyForce::Buoyancy(gravity, density_factor, expansion_factor, ambient_temperature))
+    }
+
+    // Registers an additional named scalar which is advected and extrapolated alongside density. Names must be unique,
+    // otherwise this panics
+    pub fn add_scalar(mut self, name: &str) -> Self {
+        if self.scalars.iter().any(|scalar| scalar.name == name) {
+            panic!("A scalar named {} has already been registered", name);
+        }
+
+        self.scalars.push(ScalarField::new(name, self.rows, self.columns, self.cell_size));
+        self.apply_periodicity();
+        self
+    }
+
+    // Sets the molecular diffusivity of a registered scalar
+    pub fn scalar_diffusivity(mut self, name: &str, diffusivity: f64) -> Self {
+        let index = self.scalar_index(name);
+        self.scalars[index].diffusivity = diffusivity;
+        self
+    }
+
+    // Sets the thermal diffusivity of the temperature field
+    pub fn thermal_diffusivity(mut self, diffusivity: f64) -> Self {
+        self.thermal_diffusivity = diffusivity;
+        self
+    }
+
+    // Returns the index of a registered scalar, panicking if the name is unknown
+    fn scalar_index(&self, name: &str) -> usize {
+        match self.scalars.iter().position(|scalar| scalar.name == name) {
+            Some(index) => index,
+            None => panic!("No scalar named {} has been registered", name)
+        }
+    }
+
+    // Returns the field of a registered scalar
+    pub fn scalar(&self, name: &str) -> &FluidQuantity {
+        &self.scalars[self.scalar_index(name)].quantity
+    }
+
+    // Marks every quantity as wrapping in the directions the domain boundary is periodic in
+    fn apply_periodicity(&mut self) {
+        let periodic_x = self.boundary.periodic_x();
+        let periodic_y = self.boundary.periodic_y();
+
+        let mut quantities = vec![&mut self.u_velocity, &mut self.v_velocity, &mut self.density, &mut self.temperature];
+        quantities.extend(self.scalars.iter_mut().map(|scalar| &mut scalar.quantity));
+        quantities.extend(self.liquid.iter_mut().map(|liquid| &mut liquid.distance));
+        quantities.extend(self.volume_fraction.iter_mut().map(|fraction| &mut fraction.fraction));
+
+        for quantity in quantities {
+            quantity.periodic_x = periodic_x;
+            quantity.periodic_y = periodic_y;
+        }
+    }
+
+    // Velocity of the u face at (row, column) inside a body a given depth from its surface, following the wall model
+    // of the body
+    fn u_wall_velocity(&self, row: usize, column: usize, body: usize, depth: f64) -> f64 {
+        let fluid_y = self.interpolation.run(column as f64, row as f64 + 0.5, &self.v_velocity);
+        self.bodies[body].wall_velocity(column as f64 * self.cell_size, (row as f64 + 0.5) * self.cell_size, self.u_velocity.at(row, column), fluid_y, depth).0
+    }
+
+    // Velocity of the v face at (row, column) inside a body a given depth from its surface, following the wall model
+    // of the body
+    fn v_wall_velocity(&self, row: usize, column: usize, body: usize, depth: f64) -> f64 {
+        let fluid_x = self.interpolation.run(column as f64 + 0.5, row as f64, &self.u_velocity);
+        self.bodies[body].wall_velocity((column as f64 + 0.5) * self.cell_size, row as f64 * self.cell_size, fluid_x, self.v_velocity.at(row, column), depth).1
+    }
+
+    // Whether the cells either side of a face are both solid, so that the pressure solve never acts on it. Faces on the
+    // edge of a domain that does not wrap have only one side and never count
+    fn between_solids(&self, row: usize, column: usize, horizontal: bool) -> bool {
+        let (position, count, periodic) = if horizontal {
+            (column, self.columns, self.boundary.periodic_x())
+        } else {
+            (row, self.rows, self.boundary.periodic_y())
+        };
+
+        if !periodic && (position == 0 || position == count) {
+            return false;
+        }
+
+        let cell = |position: usize| if horizontal { self.density.cell_at(row, position % count) } else { self.density.cell_at(position % count, column) };
+        cell(position + count - 1) == 1 && cell(position) == 1
+    }
+
+    // Imposes the wall model of each body on its solid faces that lie between two solid cells, or on those bordering fluid
+    fn apply_wall_models(&mut self, between: bool) {
+        if self.bodies.iter().all(|body| matches!(body.wall(), WallModel::NoSlip)) {
+            return;
+        }
+
+        let u_saved = self.u_velocity.src.clone();
+        let v_saved = self.v_velocity.src.clone();
+        self.u_velocity.extrapolate(self.extrapolation_layers);
+        self.v_velocity.extrapolate(self.extrapolation_layers);
+
+        let u_depth = self.u_velocity.solid_depth();
+        let v_depth = self.v_velocity.solid_depth();
+        let mut u_walls = Vec::new();
+        let mut v_walls = Vec::new();
+
+        for row in 0..self.u_velocity.rows {
+            for column in 0..self.u_velocity.columns {
+                if self.u_velocity.cell_at(row, column) == 1 && self.between_solids(row, column, true) == between {
+                    let depth = max(u_depth[row * self.u_velocity.columns + column], 0.0);
+                    u_walls.push((row, column, self.u_wall_velocity(row, column, self.u_velocity.body_at(row, column) as usize, depth)));
+                }
+            }
+        }
+
+        for row in 0..self.v_velocity.rows {
+            for column in 0..self.v_velocity.columns {
+                if self.v_velocity.cell_at(row, column) == 1 && self.between_solids(row, column, false) == between {
+                    let depth = max(v_depth[row * self.v_velocity.columns + column], 0.0);
+                    v_walls.push((row, column, self.v_wall_velocity(row, column, self.v_velocity.body_at(row, column) as usize, depth)));
+                }
+            }
+        }
+
+        self.u_velocity.src = u_saved;
+        self.v_velocity.src = v_saved;
+
+        for (row, column, velocity) in u_walls {
+            *self.u_velocity.at_mut(row, column) = velocity;
+        }
+
+        for (row, column, velocity) in v_walls {
+            *self.v_velocity.at_mut(row, column) = velocity;
+        }
+
+        self.u_velocity.copy_periodic_faces();
+        self.v_velocity.copy_periodic_faces();
+    }
+
+    // Sets boundaries of simulation by imposing body velocities on solid faces and the prescribed velocity on domain edges
     fn set_boundaries(&mut self) {
         for row in 0..self.rows {
             for column in 0..self.columns {
@@ -99,21 +408,208 @@ impl FluidSolver {
         }
 
         for row in 0..self.rows {
-            *self.u_velocity.at_mut(row, 0) = 0.0;
-            *self.u_velocity.at_mut(row, self.columns) = 0.0;
+            let position = (row as f64 + 0.5) / self.rows as f64;
+
+            if let Some(speed) = self.boundary.left.inflow_speed(position) {
+                *self.u_velocity.at_mut(row, 0) = speed;
+            }
+
+            if let Some(speed) = self.boundary.right.inflow_speed(position) {
+                *self.u_velocity.at_mut(row, self.columns) = -speed;
+            }
+        }
+
+        for column in 0..self.columns {
+            let position = (column as f64 + 0.5) / self.columns as f64;
+
+            if let Some(speed) = self.boundary.top.inflow_speed(position) {
+                *self.v_velocity.at_mut(0, column) = speed;
+            }
+
+            if let Some(speed) = self.boundary.bottom.inflow_speed(position) {
+                *self.v_velocity.at_mut(self.rows, column) = -speed;
+            }
+        }
+
+        self.u_velocity.copy_periodic_faces();
+        self.v_velocity.copy_periodic_faces();
+    }
+
+    // Copies the normal velocity of the interior onto open domain edges ahead of the projection. Outlets only keep the
+    // outward part, so the pressure solve cannot draw fluid back in through them
+    fn extrapolate_open_edges(&mut self) {
+        for row in 0..self.rows {
+            if self.boundary.left.is_open() {
+                let velocity = self.u_velocity.at(row, 1);
+                *self.u_velocity.at_mut(row, 0) = if self.boundary.left.is_outlet() { min(velocity, 0.0) } else { velocity };
+            }
+
+            if self.boundary.right.is_open() {
+                let velocity = self.u_velocity.at(row, self.columns - 1);
+                *self.u_velocity.at_mut(row, self.columns) = if self.boundary.right.is_outlet() { max(velocity, 0.0) } else { velocity };
+            }
         }
 
         for column in 0..self.columns {
-            *self.v_velocity.at_mut(0, column) = 0.0;
-            *self.v_velocity.at_mut(self.rows, column) = 0.0;
+            if self.boundary.top.is_open() {
+                let velocity = self.v_velocity.at(1, column);
+                *self.v_velocity.at_mut(0, column) = if self.boundary.top.is_outlet() { min(velocity, 0.0) } else { velocity };
+            }
+
+            if self.boundary.bottom.is_open() {
+                let velocity = self.v_velocity.at(self.rows - 1, column);
+                *self.v_velocity.at_mut(self.rows, column) = if self.boundary.bottom.is_outlet() { max(velocity, 0.0) } else { velocity };
+            }
         }
     }
 
+    // Integrates every registered body force into the face velocities
+    fn apply_body_forces(&mut self) {
+        for force in &self.forces {
+            force.apply(&mut self.u_velocity, &mut self.v_velocity, &self.density, &self.temperature, self.time, self.timestep);
+        }
+    }
+
+    // Spreads the elastic forces of every filament onto the velocity faces
+    fn apply_filament_forces(&mut self) {
+        for filament in &mut self.filaments {
+            filament.spread(&mut self.u_velocity, &mut self.v_velocity, &self.cell_density, self.timestep);
+        }
+    }
+
+    // Carries every filament along with the projected velocity field
+    fn move_filaments(&mut self) {
+        for filament in &mut self.filaments {
+            filament.advance(&self.u_velocity, &self.v_velocity, self.timestep);
+        }
+    }
+
+    // Relaxes the velocity inside every porous region towards the velocity of the region
+    fn apply_porous_drag(&mut self) {
+        for region in &self.porous_regions {
+            region.apply(&mut self.u_velocity, true, self.viscosity, self.timestep);
+            region.apply(&mut self.v_velocity, false, self.viscosity, self.timestep);
+        }
+
+        self.u_velocity.copy_periodic_faces();
+        self.v_velocity.copy_periodic_faces();
+    }
+
+    // Diffuses momentum implicitly, holding solid faces at their wall velocity and applying the tangential edge
+    // conditions. Faces between two solid cells already carry the wall model, those bordering fluid take it here
+    fn apply_viscosity(&mut self) {
+        if self.viscosity == 0.0 {
+            return;
+        }
+
+        self.apply_wall_models(false);
+
+        let scale = self.viscosity * self.timestep / (self.cell_size * self.cell_size);
+
+        let mut u_fixed = self.u_velocity.cell.clone();
+        for row in 0..self.u_velocity.rows {
+            for column in 0..self.u_velocity.columns {
+                if (column == 0 && !self.u_velocity.periodic_x) || column == self.columns {
+                    u_fixed[row * self.u_velocity.columns + column] = 1;
+                }
+            }
+        }
+
+        let mut v_fixed = self.v_velocity.cell.clone();
+        for row in 0..self.v_velocity.rows {
+            for column in 0..self.v_velocity.columns {
+                if (row == 0 && !self.v_velocity.periodic_y) || row == self.rows {
+                    v_fixed[row * self.v_velocity.columns + column] = 1;
+                }
+            }
+        }
+
+        let viscosity_time = Instant::now();
+        let u_walls = [0.0, 0.0, self.boundary.top.tangential_factor(), self.boundary.bottom.tangential_factor()];
+        let v_walls = [self.boundary.left.tangential_factor(), self.boundary.right.tangential_factor(), 0.0, 0.0];
+
+        diffuse(&mut self.u_velocity, &u_fixed, scale, u_walls, &mut self.linear_solver, self.iterations);
+        diffuse(&mut self.v_velocity, &v_fixed, scale, v_walls, &mut self.linear_solver, self.iterations);
+
+        self.u_velocity.copy_periodic_faces();
+        self.v_velocity.copy_periodic_faces();
+        self.record_time("Viscosity", viscosity_time);
+    }
+
+    // Marks the cells whose pressure is solved for with 0, leaving solids at 1 and, with a free surface, air at 2 where
+    // the pressure is held at zero. Two fluids are both solved for
+    fn classify_pressure_cells(&mut self) {
+        self.pressure_cells.copy_from_slice(&self.density.cell);
+
+        if self.gas_density.is_some() {
+            return;
+        }
+
+        if let Some(liquid) = &self.liquid {
+            for (element, cell) in self.pressure_cells.iter_mut().enumerate() {
+                if *cell == 0 && liquid.distance.src[element] >= 0.0 {
+                    *cell = 2;
+                    self.pressure[element] = 0.0;
+                }
+            }
+        }
+    }
+
+    // Extends the level set into solids and restores its distance property after advection, then sorts the cells into
+    // liquid and air for the pressure solve. With two fluids each cell takes the density of the mix it holds. Volume
+    // fractions replace the level set before it is extended and are used for the density directly
+    fn update_liquid(&mut self) {
+        if let Some(liquid) = &mut self.liquid {
+            if let Some(fraction) = &self.volume_fraction {
+                fraction.fill_level_set(liquid);
+            }
+
+            liquid.distance.copy_solid_fields(&self.density);
+            liquid.distance.extrapolate(self.extrapolation_layers);
+            liquid.redistance();
+
+            if let Some(gas_density) = self.gas_density {
+                for (element, density) in self.cell_density.iter_mut().enumerate() {
+                    let (row, column) = (element / self.columns, element % self.columns);
+                    let fraction = match &self.volume_fraction {
+                        Some(fraction) => fraction.at(row, column),
+                        None => liquid.fraction(row, column)
+                    };
+
+                    *density = gas_density + (self.fluid_density - gas_density) * fraction;
+                }
+            }
+        }
+
+        self.classify_pressure_cells();
+    }
+
+    // Density on the face at (row, column), the average of the cells either side. Domain edges take the cell inside,
+    // while periodic edges average across the seam
+    fn face_density(&self, row: usize, column: usize, horizontal: bool) -> f64 {
+        let (first, second) = if horizontal {
+            let before = if column > 0 { column - 1 } else if self.boundary.periodic_x() { self.columns - 1 } else { 0 };
+            let after = if column < self.columns { column } else if self.boundary.periodic_x() { 0 } else { self.columns - 1 };
+            (row * self.columns + before, row * self.columns + after)
+        } else {
+            let before = if row > 0 { row - 1 } else if self.boundary.periodic_y() { self.rows - 1 } else { 0 };
+            let after = if row < self.rows { row } else if self.boundary.periodic_y() { 0 } else { self.rows - 1 };
+            (before * self.columns + column, after * self.columns + column)
+        };
+
+        0.5 * (self.cell_density[first] + self.cell_density[second])
+    }
+
+    // Converts a pressure difference across the face at (row, column) into a change in velocity over one cell
+    fn pressure_scale(&self, row: usize, column: usize, horizontal: bool) -> f64 {
+        self.timestep / (self.face_density(row, column, horizontal) * self.cell_size)
+    }
+
     // Calculates residual vector from uv vector field
     fn calculate_residual(&mut self) {
         for row in 0..self.rows {
             for column in 0..self.columns {
-                if self.density.cell_at(row, column) == 0 {
+                if self.pressure_cells[row * self.columns + column] == 0 {
                     // Get x and x+1 velocities
                     let u1 = self.u_velocity.at(row, column) * self.u_velocity.volume_at(row, column);
                     let u2 = self.u_velocity.at(row, column + 1) * self.u_velocity.volume_at(row, column + 1);
@@ -123,26 +619,54 @@ impl FluidSolver {
                     let v2 = self.v_velocity.at(row + 1, column) * self.v_velocity.volume_at(row + 1, column);
 
                     // Factor in cell scale and invert for solving
-                    self.residual[row * self.columns + column] = -1.0 * (u2 - u1 + v2 - v1) / self.cell_size;
+                    self.residual[row * self.columns + column] = -(u2 - u1 + v2 - v1) / self.cell_size;
 
                     if !self.bodies.is_empty() {
-                        let vol = self.density.volume_at(row, column);
+                        // The solid part of each face moves with the body covering it. Faces entirely open have no body
+                        // near enough to look up
                         let index = row * self.columns + column;
 
-                        if column > 0 {
-                            self.residual[index] -= (self.u_velocity.volume_at(row, column) - vol) * self.bodies[self.density.body_at(row, column - 1) as usize].velocity_x(column as f64 * self.cell_size, (row as f64 + 0.5) * self.cell_size);
+                        if column > 0 && self.u_velocity.volume_at(row, column) < 1.0 {
+                            self.residual[index] += (1.0 - self.u_velocity.volume_at(row, column)) * self.bodies[self.density.body_at(row, column - 1) as usize].velocity_x(column as f64 * self.cell_size, (row as f64 + 0.5) * self.cell_size) / self.cell_size;
+                        }
+
+                        if row > 0 && self.v_velocity.volume_at(row, column) < 1.0 {
+                            self.residual[index] += (1.0 - self.v_velocity.volume_at(row, column)) * self.bodies[self.density.body_at(row - 1, column) as usize].velocity_y((column as f64 + 0.5) * self.cell_size, row as f64 * self.cell_size) / self.cell_size;
+                        }
+
+                        if column < self.columns - 1 && self.u_velocity.volume_at(row, column + 1) < 1.0 {
+                            self.residual[index] -= (1.0 - self.u_velocity.volume_at(row, column + 1)) * self.bodies[self.density.body_at(row, column + 1) as usize].velocity_x((column as f64 + 1.0) * self.cell_size, (row as f64 + 0.5) * self.cell_size) / self.cell_size;
+                        }
+
+                        if row < self.rows - 1 && self.v_velocity.volume_at(row + 1, column) < 1.0 {
+                            self.residual[index] -= (1.0 - self.v_velocity.volume_at(row + 1, column)) * self.bodies[self.density.body_at(row + 1, column) as usize].velocity_y((column as f64 + 0.5) * self.cell_size, (row as f64 + 1.0) * self.cell_size) / self.cell_size;
+                        }
+                    }
+
+                    // Fixed pressure edges move their ghost cell pressure to the right hand side
+                    let index = row * self.columns + column;
+
+                    if column == 0 {
+                        if let Some(pressure) = self.boundary.left.pressure() {
+                            self.residual[index] += self.pressure_scale(row, 0, true) / self.cell_size * self.u_velocity.volume_at(row, 0) * pressure;
                         }
+                    }
 
-                        if row > 0 {
-                            self.residual[index] -= (self.v_velocity.volume_at(row, column) - vol) * self.bodies[self.density.body_at(row - 1, column) as usize].velocity_y((column as f64 + 0.5) * self.cell_size, row as f64 * self.cell_size);
+                    if column == self.columns - 1 {
+                        if let Some(pressure) = self.boundary.right.pressure() {
+                            self.residual[index] += self.pressure_scale(row, self.columns, true) / self.cell_size * self.u_velocity.volume_at(row, self.columns) * pressure;
                         }
+                    }
 
-                        if column < self.columns - 1 {
-                            self.residual[index] += (self.u_velocity.volume_at(row, column + 1) - vol) * self.bodies[self.density.body_at(row, column + 1) as usize].velocity_x((column as f64 + 1.0) * self.cell_size, (row as f64 + 0.5) * self.cell_size);
+                    if row == 0 {
+                        if let Some(pressure) = self.boundary.top.pressure() {
+                            self.residual[index] += self.pressure_scale(0, column, false) / self.cell_size * self.v_velocity.volume_at(0, column) * pressure;
                         }
+                    }
 
-                        if row < self.rows - 1 {
-                            self.residual[index] += (self.v_velocity.volume_at(row + 1, column) - vol) * self.bodies[self.density.body_at(row + 1, column) as usize].velocity_y((column as f64 + 0.5) * self.cell_size, (row as f64 + 1.0) * self.cell_size);
+                    if row == self.rows - 1 {
+                        if let Some(pressure) = self.boundary.bottom.pressure() {
+                            self.residual[index] += self.pressure_scale(self.rows, column, false) / self.cell_size * self.v_velocity.volume_at(self.rows, column) * pressure;
                         }
                     }
                 } else {
@@ -151,44 +675,443 @@ impl FluidSolver {
 
             }
         }
+
+        // Surface tension holds the surface at a pressure set by its curvature, which the ghost fluid faces carry over.
+        // Between two fluids it is a jump in pressure across the face, raising the liquid side and lowering the other
+        if self.surface_tension > 0.0 {
+            if let Some(level_set) = &self.liquid {
+                for (liquid, air, row, column, horizontal, _) in self.surface_faces() {
+                    let open = if horizontal { self.u_velocity.volume_at(row, column) } else { self.v_velocity.volume_at(row, column) };
+                    let factor = self.pressure_scale(row, column, horizontal) / self.cell_size * open;
+                    let jump = self.surface_pressure(liquid, air);
+
+                    if self.gas_density.is_some() {
+                        self.residual[liquid] += factor * jump;
+                        self.residual[air] -= factor * jump;
+                    } else {
+                        self.residual[liquid] += factor * jump / crossing(level_set.distance.src[liquid], level_set.distance.src[air]);
+                    }
+                }
+            }
+        }
+
+        // Without a fixed pressure anywhere the system is singular and only solvable when the net divergence vanishes,
+        // which moving bodies break slightly through their discretised surface. A free surface fixes the pressure too
+        let edges = [&self.boundary.left, &self.boundary.right, &self.boundary.top, &self.boundary.bottom];
+        if edges.iter().all(|edge| edge.pressure().is_none()) && !self.pressure_cells.contains(&2) {
+            let (total, count) = self.residual.iter().zip(&self.pressure_cells)
+                .filter(|(_, &cell)| cell == 0)
+                .fold((0.0, 0.0), |(total, count), (residual, _)| (total + residual, count + 1.0));
+
+            if count > 0.0 {
+                for (residual, &cell) in self.residual.iter_mut().zip(&self.pressure_cells) {
+                    if cell == 0 {
+                        *residual -= total / count;
+                    }
+                }
+            }
+        }
+    }
+
+    // Builds the pressure matrix, weighting each fluid-fluid face by its open fraction over its density. Faces from
+    // liquid to air hold the zero surface pressure where the level set crosses, scaling the face weight by the inverse
+    // of that distance
+    fn build_pressure_matrix(&mut self) {
+        let (rows, columns) = (self.rows, self.columns);
+        let cell = &self.pressure_cells;
+        let level_set = &self.liquid;
+        let surface = |liquid: usize, air: usize| match level_set {
+            Some(level_set) => 1.0 / crossing(level_set.distance.src[liquid], level_set.distance.src[air]),
+            None => 0.0
+        };
+
+        self.a = Sparse::new(rows * columns);
+        self.a.periodic_x = self.boundary.periodic_x();
+        self.a.periodic_y = self.boundary.periodic_y();
+
+        for row in 0..rows {
+            for column in 0..columns {
+                let element = row * columns + column;
+
+                // In periodic directions the last cell couples to the first through the shared edge face
+                let right = if column < columns - 1 { Some(element + 1) } else if self.a.periodic_x { Some(element + 1 - columns) } else { None };
+                let below = if row < rows - 1 { Some(element + columns) } else if self.a.periodic_y { Some(element - (rows - 1) * columns) } else { None };
+                let faces = [
+                    (right, self.pressure_scale(row, column + 1, true) / self.cell_size * self.u_velocity.volume_at(row, column + 1), true),
+                    (below, self.pressure_scale(row + 1, column, false) / self.cell_size * self.v_velocity.volume_at(row + 1, column), false)
+                ];
+
+                for &(neighbour, factor, horizontal) in &faces {
+                    let neighbour = match neighbour {
+                        Some(neighbour) => neighbour,
+                        None => continue
+                    };
+
+                    match (cell[element], cell[neighbour]) {
+                        (0, 0) => {
+                            self.a.diagonals[element] += factor;
+                            self.a.diagonals[neighbour] += factor;
+
+                            if horizontal {
+                                self.a.plus_x[element] = -factor;
+                            } else {
+                                self.a.plus_y[element] = -factor;
+                            }
+                        }
+                        (0, 2) => self.a.diagonals[element] += factor * surface(element, neighbour),
+                        (2, 0) => self.a.diagonals[neighbour] += factor * surface(neighbour, element),
+                        _ => {}
+                    }
+                }
+
+                if cell[element] == 0 {
+                    // Edges with a fixed pressure couple to a ghost cell outside the domain
+                    if column == 0 && self.boundary.left.pressure().is_some() {
+                        self.a.diagonals[element] += self.pressure_scale(row, 0, true) / self.cell_size * self.u_velocity.volume_at(row, 0);
+                    }
+
+                    if column == columns - 1 && self.boundary.right.pressure().is_some() {
+                        self.a.diagonals[element] += self.pressure_scale(row, columns, true) / self.cell_size * self.u_velocity.volume_at(row, columns);
+                    }
+
+                    if row == 0 && self.boundary.top.pressure().is_some() {
+                        self.a.diagonals[element] += self.pressure_scale(0, column, false) / self.cell_size * self.v_velocity.volume_at(0, column);
+                    }
+
+                    if row == rows - 1 && self.boundary.bottom.pressure().is_some() {
+                        self.a.diagonals[element] += self.pressure_scale(rows, column, false) / self.cell_size * self.v_velocity.volume_at(rows, column);
+                    }
+                }
+            }
+        }
     }
 
     // Solves pressure array based on divergence, passed to linear solver function
     fn solve_pressure(&mut self) {
         let pressure_time = Instant::now();
+        self.build_pressure_matrix();
         self.linear_solver.solve(&mut self.pressure,
                                  &mut self.residual,
                                  &mut self.auxiliary,
                                  &mut self.search,
                                  &mut self.preconditioner,
-                                 &mut self.a,
-                                 &self.density.cell,
-                                 self.fluid_density,
-                                 self.timestep,
-                                 self.cell_size,
+                                 &self.a,
+                                 &self.pressure_cells,
                                  self.rows,
                                  self.columns,
-                                 self.iterations,
-                                 &self.u_velocity,
-                                 &self.v_velocity);
-        print!("Linear Solve: {} ms, ", pressure_time.elapsed().as_millis())
+                                 self.iterations);
+        self.record_time("Linear Solve", pressure_time);
     }
 
-    // Applies computed pressure field to the xy velocity vector field
+    // Applies computed pressure field to the xy velocity vector field. Domain edge faces only change where the pressure
+    // outside is fixed or the edge is periodic, in which case the far duplicate is refreshed by set_boundaries
     fn apply_pressure(&mut self) {
-        let scale = self.timestep / (self.fluid_density * self.cell_size);
+        let left = self.boundary.left.pressure();
+        let right = self.boundary.right.pressure();
+        let top = self.boundary.top.pressure();
+        let bottom = self.boundary.bottom.pressure();
+        let periodic_x = self.boundary.periodic_x();
+        let periodic_y = self.boundary.periodic_y();
 
         for row in 0..self.rows {
             for column in 0..self.columns {
-                if self.density.cell_at(row, column) == 0 {
+                if self.pressure_cells[row * self.columns + column] == 0 {
                     let element = row * self.columns + column;
-                    *self.u_velocity.at_mut(row, column) -= scale * self.pressure[element];
-                    *self.u_velocity.at_mut(row, column + 1) += scale * self.pressure[element];
-                    *self.v_velocity.at_mut(row, column) -= scale * self.pressure[element];
-                    *self.v_velocity.at_mut(row + 1, column) += scale * self.pressure[element];
+                    let (scale_left, scale_right) = (self.pressure_scale(row, column, true), self.pressure_scale(row, column + 1, true));
+                    let (scale_top, scale_bottom) = (self.pressure_scale(row, column, false), self.pressure_scale(row + 1, column, false));
+
+                    if column > 0 {
+                        *self.u_velocity.at_mut(row, column) -= scale_left * self.pressure[element];
+                    } else if periodic_x {
+                        *self.u_velocity.at_mut(row, column) -= scale_left * (self.pressure[element] - self.pressure[element + self.columns - 1]);
+                    } else if let Some(pressure) = left {
+                        *self.u_velocity.at_mut(row, column) -= scale_left * (self.pressure[element] - pressure);
+                    }
+
+                    if column < self.columns - 1 {
+                        *self.u_velocity.at_mut(row, column + 1) += scale_right * self.pressure[element];
+                    } else if let Some(pressure) = right {
+                        *self.u_velocity.at_mut(row, column + 1) += scale_right * (self.pressure[element] - pressure);
+                    }
+
+                    if row > 0 {
+                        *self.v_velocity.at_mut(row, column) -= scale_top * self.pressure[element];
+                    } else if periodic_y {
+                        *self.v_velocity.at_mut(row, column) -= scale_top * (self.pressure[element] - self.pressure[element + (self.rows - 1) * self.columns]);
+                    } else if let Some(pressure) = top {
+                        *self.v_velocity.at_mut(row, column) -= scale_top * (self.pressure[element] - pressure);
+                    }
+
+                    if row < self.rows - 1 {
+                        *self.v_velocity.at_mut(row + 1, column) += scale_bottom * self.pressure[element];
+                    } else if let Some(pressure) = bottom {
+                        *self.v_velocity.at_mut(row + 1, column) += scale_bottom * (self.pressure[element] - pressure);
+                    }
                 }
             }
         }
+
+        self.apply_surface_pressure();
+    }
+
+    // Faces between a liquid and an air cell as (liquid, air, face row, face column, horizontal, sign), where sign is
+    // 1 when the liquid lies before the face along its axis and -1 after it. Periodic seams use the first stored face.
+    // With two fluids the second fluid takes the place of the air
+    fn surface_faces(&self) -> Vec<(usize, usize, usize, usize, bool, f64)> {
+        let mut faces = Vec::new();
+
+        let level_set = match &self.liquid {
+            Some(level_set) => level_set,
+            None => return faces
+        };
+
+        // Liquid cells are 0, cells of air or the second fluid 2 and solids 1
+        let phase = |element: usize| match self.pressure_cells[element] {
+            1 => 1,
+            _ if level_set.distance.src[element] < 0.0 => 0,
+            _ => 2
+        };
+
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let element = row * self.columns + column;
+
+                let right = if column < self.columns - 1 { Some((element + 1, column + 1)) } else if self.boundary.periodic_x() { Some((element + 1 - self.columns, 0)) } else { None };
+                let below = if row < self.rows - 1 { Some((element + self.columns, row + 1)) } else if self.boundary.periodic_y() { Some((element - (self.rows - 1) * self.columns, 0)) } else { None };
+
+                if let Some((neighbour, face)) = right {
+                    match (phase(element), phase(neighbour)) {
+                        (0, 2) => faces.push((element, neighbour, row, face, true, 1.0)),
+                        (2, 0) => faces.push((neighbour, element, row, face, true, -1.0)),
+                        _ => {}
+                    }
+                }
+
+                if let Some((neighbour, face)) = below {
+                    match (phase(element), phase(neighbour)) {
+                        (0, 2) => faces.push((element, neighbour, face, column, false, 1.0)),
+                        (2, 0) => faces.push((neighbour, element, face, column, false, -1.0)),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        faces
+    }
+
+    // Pressure the surface holds between a liquid and an air cell, zero without surface tension. Volume fractions give
+    // the curvature from height functions where they can, falling back on the level set
+    fn surface_pressure(&self, liquid: usize, air: usize) -> f64 {
+        let level_set = match &self.liquid {
+            Some(level_set) if self.surface_tension > 0.0 => level_set,
+            _ => return 0.0
+        };
+
+        let curvature = match &self.volume_fraction {
+            Some(fraction) => fraction.surface_curvature(liquid, air).unwrap_or_else(|| level_set.surface_curvature(liquid, air)),
+            None => level_set.surface_curvature(liquid, air)
+        };
+
+        self.surface_tension * curvature
+    }
+
+    // Completes the pressure gradient across faces between liquid and air. The air side holds a ghost pressure on the
+    // line from the liquid cell through the surface pressure where the level set crosses, rather than the zero the air
+    // cells store. Between two fluids both sides are already applied and only the surface tension jump is added
+    fn apply_surface_pressure(&mut self) {
+        let level_set = match &self.liquid {
+            Some(level_set) => level_set,
+            None => return
+        };
+
+        if self.gas_density.is_some() && self.surface_tension == 0.0 {
+            return;
+        }
+
+        let mut updates = Vec::new();
+
+        for (liquid, air, row, column, horizontal, sign) in self.surface_faces() {
+            let scale = self.pressure_scale(row, column, horizontal);
+
+            let ghost = if self.gas_density.is_some() {
+                self.surface_pressure(liquid, air)
+            } else {
+                let fraction = crossing(level_set.distance.src[liquid], level_set.distance.src[air]);
+                self.pressure[liquid] + (self.surface_pressure(liquid, air) - self.pressure[liquid]) / fraction
+            };
+
+            updates.push((row, column, horizontal, -sign * scale * ghost));
+        }
+
+        for (row, column, horizontal, change) in updates {
+            if horizontal {
+                *self.u_velocity.at_mut(row, column) += change;
+            } else {
+                *self.v_velocity.at_mut(row, column) += change;
+            }
+        }
+    }
+
+    // Gradient of the pressure at a solved cell from its solved neighbours, one sided where only one side is solved
+    fn pressure_gradient(&self, row: usize, column: usize) -> (f64, f64) {
+        let pressure = self.pressure[row * self.columns + column];
+        let solved = |row: Option<usize>, column: Option<usize>| match (row, column) {
+            (Some(row), Some(column)) if self.pressure_cells[row * self.columns + column] == 0 => Some(self.pressure[row * self.columns + column]),
+            _ => None
+        };
+
+        let before = |position: usize, count: usize, periodic: bool| if position > 0 { Some(position - 1) } else if periodic { Some(count - 1) } else { None };
+        let after = |position: usize, count: usize, periodic: bool| if position + 1 < count { Some(position + 1) } else if periodic { Some(0) } else { None };
+
+        let (periodic_x, periodic_y) = (self.boundary.periodic_x(), self.boundary.periodic_y());
+        let left = solved(Some(row), before(column, self.columns, periodic_x));
+        let right = solved(Some(row), after(column, self.columns, periodic_x));
+        let top = solved(before(row, self.rows, periodic_y), Some(column));
+        let bottom = solved(after(row, self.rows, periodic_y), Some(column));
+
+        let difference = |first: Option<f64>, second: Option<f64>| match (first, second) {
+            (Some(first), Some(second)) => 0.5 * (second - first) / self.cell_size,
+            (Some(first), None) => (pressure - first) / self.cell_size,
+            (None, Some(second)) => (second - pressure) / self.cell_size,
+            (None, None) => 0.0
+        };
+
+        (difference(left, right), difference(top, bottom))
+    }
+
+    // Pressure at a cell centre, carried linearly from the solved neighbours of cut cells that are too small to be solved
+    fn cut_cell_pressure(&self, row: usize, column: usize) -> Option<f64> {
+        if self.pressure_cells[row * self.columns + column] != 1 {
+            return Some(self.pressure[row * self.columns + column]);
+        }
+
+        let (mut total, mut count) = (0.0, 0.0);
+
+        for (row_offset, column_offset) in [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (-1, 1), (1, -1), (1, 1)] {
+            let (neighbour_row, neighbour_column) = (row as isize + row_offset, column as isize + column_offset);
+            let inside_x = self.density.periodic_x || (0..self.columns as isize).contains(&neighbour_column);
+            let inside_y = self.density.periodic_y || (0..self.rows as isize).contains(&neighbour_row);
+
+            if !inside_x || !inside_y {
+                continue;
+            }
+
+            let neighbour_row = self.density.wrap_row(neighbour_row);
+            let neighbour_column = self.density.wrap_column(neighbour_column);
+
+            if self.pressure_cells[neighbour_row * self.columns + neighbour_column] != 0 {
+                continue;
+            }
+
+            let (gradient_x, gradient_y) = self.pressure_gradient(neighbour_row, neighbour_column);
+            let pressure = self.pressure[neighbour_row * self.columns + neighbour_column];
+            total += pressure - (gradient_x * column_offset as f64 + gradient_y * row_offset as f64) * self.cell_size;
+            count += 1.0;
+        }
+
+        if count > 0.0 { Some(total / count) } else { None }
+    }
+
+    // Integrates pressure and wall shear over the embedded surface of each cut cell into the force on every body
+    fn integrate_body_forces(&mut self) {
+        // Pressure is only defined up to a constant in closed domains and the discrete torque arms of a body do not balance
+        // exactly, so the pressure terms are measured against the mean pressure around each body
+        let mut pressure_forces = vec![(0.0, 0.0, 0.0); self.bodies.len()];
+        let mut surface_areas = vec![(0.0, 0.0, 0.0); self.bodies.len()];
+        let mut mean_pressures = vec![(0.0, 0.0); self.bodies.len()];
+
+        for force in &mut self.body_forces {
+            *force = (0.0, 0.0, 0.0);
+        }
+
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let area_x = -(self.u_velocity.volume_at(row, column + 1) - self.u_velocity.volume_at(row, column)) * self.cell_size;
+                let area_y = -(self.v_velocity.volume_at(row + 1, column) - self.v_velocity.volume_at(row, column)) * self.cell_size;
+
+                if area_x == 0.0 && area_y == 0.0 {
+                    continue;
+                }
+
+                // Cut cells too small to be solved still carry their share of the surface, so that the pressure sums by
+                // parts into the same discrete volume as the open fractions and a hydrostatic pressure gives exact buoyancy
+                let (index, pressure) = match self.cut_cell_pressure(row, column) {
+                    Some(pressure) if self.density.body_at(row, column) != NO_BODY => (self.density.body_at(row, column) as usize, pressure),
+                    _ => continue
+                };
+                let body = &self.bodies[index];
+
+                let x = (column as f64 + 0.5) * self.cell_size;
+                let y = (row as f64 + 0.5) * self.cell_size;
+
+                let (mut surface_x, mut surface_y) = (x, y);
+                body.closest_surface_point(&mut surface_x, &mut surface_y);
+
+                let total = &mut pressure_forces[index];
+                total.0 += pressure * area_x;
+                total.1 += pressure * area_y;
+                total.2 += body.torque(surface_x, surface_y, pressure * area_x, pressure * area_y);
+
+                let total = &mut surface_areas[index];
+                total.0 += area_x;
+                total.1 += area_y;
+                total.2 += body.torque(surface_x, surface_y, area_x, area_y);
+
+                mean_pressures[index].0 += pressure;
+                mean_pressures[index].1 += 1.0;
+
+                let dynamic_viscosity = self.viscosity * self.cell_density[row * self.columns + column];
+
+                if dynamic_viscosity > 0.0 && self.density.cell_at(row, column) == 0 {
+                    // Shear from the tangential velocity of the fluid relative to the wall across the gap to the surface
+                    let distance = max(body.distance(x, y), 0.5 * self.cell_size);
+                    let fluid_x = self.interpolation.run(column as f64 + 0.5, row as f64 + 0.5, &self.u_velocity);
+                    let fluid_y = self.interpolation.run(column as f64 + 0.5, row as f64 + 0.5, &self.v_velocity);
+                    let (wall_x, wall_y) = body.wall_velocity(x, y, fluid_x, fluid_y, distance);
+                    let (normal_x, normal_y) = (self.density.normal_x_at(row, column), self.density.normal_y_at(row, column));
+
+                    let relative_x = fluid_x - wall_x;
+                    let relative_y = fluid_y - wall_y;
+                    let normal = relative_x * normal_x + relative_y * normal_y;
+                    let area = (area_x * area_x + area_y * area_y).sqrt();
+
+                    let force_x = dynamic_viscosity * (relative_x - normal * normal_x) / distance * area;
+                    let force_y = dynamic_viscosity * (relative_y - normal * normal_y) / distance * area;
+
+                    let total = &mut self.body_forces[index];
+                    total.0 += force_x;
+                    total.1 += force_y;
+                    total.2 += body.torque(surface_x, surface_y, force_x, force_y);
+                }
+            }
+        }
+
+        for (index, total) in self.body_forces.iter_mut().enumerate() {
+            let (pressure, samples) = mean_pressures[index];
+            let mean = if samples > 0.0 { pressure / samples } else { 0.0 };
+
+            total.0 += pressure_forces[index].0 - mean * surface_areas[index].0;
+            total.1 += pressure_forces[index].1 - mean * surface_areas[index].1;
+            total.2 += pressure_forces[index].2 - mean * surface_areas[index].2;
+        }
+    }
+
+    // Accelerates free bodies by the fluid forces from the last projection and the registered body forces. The added
+    // mass estimate assumes the body is surrounded by the fluid of the fluid density, even between two fluids
+    fn move_bodies(&mut self) {
+        let (time, columns, cell_size, fluid_density) = (self.time, self.columns, self.cell_size, self.fluid_density);
+
+        for (body, force) in self.bodies.iter_mut().zip(&self.body_forces) {
+            let (x, y) = body.position();
+
+            let acceleration = self.forces.iter().fold((0.0, 0.0), |total, body_force| {
+                let (acceleration_x, acceleration_y) = body_force.acceleration(x, y, time, columns, cell_size);
+                (total.0 + acceleration_x, total.1 + acceleration_y)
+            });
+
+            body.accelerate(force.0, force.1, force.2, acceleration, fluid_density, self.timestep);
+        }
     }
 
     // Projection method implements each step of the calculation
@@ -197,44 +1120,296 @@ impl FluidSolver {
         self.solve_pressure();
         self.apply_pressure();
 
-        self.u_velocity.extrapolate();
-        self.v_velocity.extrapolate();
-        self.density.extrapolate();
+        // Both fluids carry their own velocity, while air above a free surface takes that of the liquid
+        if let (Some(liquid), None) = (&self.liquid, self.gas_density) {
+            liquid.extrapolate_velocity(&mut self.u_velocity, true, self.extrapolation_layers);
+            liquid.extrapolate_velocity(&mut self.v_velocity, false, self.extrapolation_layers);
+        }
+
+        self.u_velocity.extrapolate(self.extrapolation_layers);
+        self.v_velocity.extrapolate(self.extrapolation_layers);
+        self.density.extrapolate(self.extrapolation_layers);
+        self.temperature.extrapolate(self.extrapolation_layers);
+
+        for scalar in &mut self.scalars {
+            scalar.quantity.extrapolate(self.extrapolation_layers);
+        }
     }
 
     // Advection method moves density scalar field through velocity vector field to produce output
     fn advect(&mut self) {
         let advect_time = Instant::now();
-        self.advection.advect(&mut self.u_velocity, &mut self.v_velocity, &mut self.density, self.timestep, &self.interpolation, &self.integration);
-        print!("Advection: {} ms, ", advect_time.elapsed().as_millis())
+
+        // Volume fractions are moved geometrically by the velocity before it is advected, and the level set is then
+        // rebuilt from them rather than advected
+        if let Some(fraction) = &mut self.volume_fraction {
+            let air: Vec<bool> = self.pressure_cells.iter().map(|&cell| cell == 2).collect();
+            fraction.advect(&self.u_velocity, &self.v_velocity, self.timestep, &air);
+        }
+
+        let mut scalars: Vec<&mut FluidQuantity> = vec![&mut self.density, &mut self.temperature];
+        scalars.extend(self.scalars.iter_mut().map(|scalar| &mut scalar.quantity));
+
+        if self.volume_fraction.is_none() {
+            scalars.extend(self.liquid.iter_mut().map(|liquid| &mut liquid.distance));
+        }
+
+        self.advection.advect(&mut self.u_velocity, &mut self.v_velocity, &mut scalars, self.timestep, &self.interpolation, &self.integration);
+        self.record_time("Advection", advect_time);
+    }
+
+    // Diffuses temperature and registered scalars according to their diffusivities
+    fn diffuse_scalars(&mut self) {
+        let diffusion_time = Instant::now();
+        let scale = self.timestep / (self.cell_size * self.cell_size);
+
+        // Bodies that exchange heat are solved together with the fluid, which is always implicit
+        let conjugate = self.bodies.iter().any(|body| !matches!(body.thermal(), ThermalCondition::Adiabatic));
+
+        if self.thermal_diffusivity > 0.0 && conjugate {
+            let problem = ConjugateProblem {
+                x_faces: &self.u_velocity,
+                y_faces: &self.v_velocity,
+                bodies: &self.bodies,
+                diffusivity: self.thermal_diffusivity,
+                timestep: self.timestep
+            };
+
+            diffuse_conjugate(&mut self.temperature, &mut self.solid_temperature, &problem, &mut self.heat_transfer, &mut self.linear_solver, self.iterations);
+        } else if self.thermal_diffusivity > 0.0 {
+            self.diffusion.diffuse_scalar(&mut self.temperature, &self.u_velocity, &self.v_velocity, self.thermal_diffusivity * scale, &mut self.linear_solver, self.iterations);
+        }
+
+        for scalar in &mut self.scalars {
+            if scalar.diffusivity > 0.0 {
+                self.diffusion.diffuse_scalar(&mut scalar.quantity, &self.u_velocity, &self.v_velocity, scalar.diffusivity * scale, &mut self.linear_solver, self.iterations);
+            }
+        }
+        self.record_time("Diffusion", diffusion_time);
+    }
+
+    // Longest step that resolves capillary waves, which must not cross a cell in one step even at the shortest
+    // wavelength the grid holds. Between two fluids the waves carry the mean of their densities. Unlimited without
+    // surface tension
+    pub fn capillary_timestep(&self) -> f64 {
+        let density = match self.gas_density {
+            Some(gas_density) => 0.5 * (self.fluid_density + gas_density),
+            None => self.fluid_density
+        };
+
+        if self.surface_tension > 0.0 && self.liquid.is_some() {
+            (density * self.cell_size.powi(3) / (2.0 * std::f64::consts::PI * self.surface_tension)).sqrt()
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    // Adds the time spent in a stage since start to its total for the current update
+    fn record_time(&mut self, stage: &'static str, start: Instant) {
+        let elapsed = start.elapsed();
+
+        match self.timings.iter_mut().find(|(name, _)| *name == stage) {
+            Some(timing) => timing.1 += elapsed,
+            None => self.timings.push((stage, elapsed))
+        }
     }
 
-    // Produces the next frame of the simulation by projecting then advecting
+    // Produces the next frame of the simulation, split into equal substeps where the timestep is longer than the
+    // capillary limit. The time spent in each stage is summed over the substeps and printed once
     pub fn update(&mut self) {
         let total_time = Instant::now();
+        let timestep = self.timestep;
+        let substeps = max((timestep / self.capillary_timestep()).ceil(), 1.0) as usize;
+
+        self.timestep = timestep / substeps as f64;
+
+        self.timings.clear();
+
+        for _ in 0..substeps {
+            self.step();
+        }
+
+        self.timestep = timestep;
+
+        for (stage, elapsed) in &self.timings {
+            print!("{}: {} ms, ", stage, elapsed.as_millis());
+        }
+
+        println!("Total: {} ms", total_time.elapsed().as_millis())
+    }
+
+    // Advances the simulation by one timestep by projecting then advecting
+    fn step(&mut self) {
         for body in &mut self.bodies {
-            body.update(self.timestep);
+            body.update(self.time, self.timestep);
+        }
+
+        for region in &mut self.porous_regions {
+            region.body.update(self.time, self.timestep);
         }
 
+        let walls = [self.boundary.left.is_solid(), self.boundary.right.is_solid(), self.boundary.top.is_solid(), self.boundary.bottom.is_solid()];
+        resolve_collisions(&mut self.bodies, self.columns as f64 * self.cell_size, self.rows as f64 * self.cell_size, walls, self.restitution);
+
         self.u_velocity.fill_solid_fields(&self.bodies);
         self.v_velocity.fill_solid_fields(&self.bodies);
         self.density.fill_solid_fields(&self.bodies);
+        self.temperature.copy_solid_fields(&self.density);
+
+        for scalar in &mut self.scalars {
+            scalar.quantity.copy_solid_fields(&self.density);
+        }
 
+        if let Some(fraction) = &mut self.volume_fraction {
+            fraction.fraction.copy_solid_fields(&self.density);
+        }
+
+        self.update_liquid();
+        self.apply_body_forces();
+        self.apply_filament_forces();
+        self.apply_porous_drag();
+        self.extrapolate_open_edges();
         self.set_boundaries();
+        self.apply_wall_models(true);
+        self.apply_viscosity();
         self.project();
+        self.integrate_body_forces();
         self.set_boundaries();
+        self.apply_wall_models(true);
+        self.move_filaments();
         self.advect();
-        println!("Total: {} ms", total_time.elapsed().as_millis())
+        self.diffuse_scalars();
+        self.move_bodies();
+
+        self.time += self.timestep;
+    }
+
+    // Returns the bodies in the simulation with their current position and velocity
+    pub fn bodies(&self) -> &[SolidBody] {
+        &self.bodies
+    }
+
+    // Force (x, y) and moment the fluid exerted on a body during the last step, from the pressure and wall shear
+    // over its surface. The moment is about the body centre, positive in the direction its theta increases
+    pub fn body_force(&self, body: usize) -> (f64, f64, f64) {
+        self.body_forces[body]
+    }
+
+    // Forces and moments on every body in the order they were given to the solver
+    pub fn body_forces(&self) -> &[(f64, f64, f64)] {
+        &self.body_forces
     }
 
+    // Drag, lift and moment coefficients of a body for a free stream along +x. Lift is positive towards -y, which
+    // is up in the images, and the moment is normalised by the square of the reference length. The dynamic pressure
+    // takes the fluid density, which between two fluids is that of the first
+    pub fn force_coefficients(&self, body: usize, reference_velocity: f64, reference_length: f64) -> (f64, f64, f64) {
+        let (force_x, force_y, moment) = self.body_forces[body];
+        let dynamic_pressure = 0.5 * self.fluid_density * reference_velocity * reference_velocity;
+
+        (force_x / (dynamic_pressure * reference_length),
+         -force_y / (dynamic_pressure * reference_length),
+         moment / (dynamic_pressure * reference_length * reference_length))
+    }
+
+    // Heat flowing from a body into the fluid per unit time during the last step, in temperature times area over time
+    pub fn heat_flow(&self, body: usize) -> f64 {
+        self.heat_transfer[body].0
+    }
+
+    // Mean temperature over the surface of a body during the last step
+    pub fn surface_temperature(&self, body: usize) -> f64 {
+        self.heat_transfer[body].2
+    }
+
+    // Nusselt number of a body, the mean heat flux over its surface relative to pure conduction across the reference
+    // length from the mean surface temperature to the reference temperature. It is undefined for a body with no surface
+    // in the fluid, no diffusivity or a surface at the reference temperature, and zero is returned instead
+    pub fn nusselt_number(&self, body: usize, reference_temperature: f64, reference_length: f64) -> f64 {
+        let (flow, area, surface) = self.heat_transfer[body];
+        let conduction = self.thermal_diffusivity * area * (surface - reference_temperature);
+
+        if conduction == 0.0 {
+            return 0.0;
+        }
+
+        flow * reference_length / conduction
+    }
+
+    // Temperature held in the cells of conducting bodies, NaN outside them
+    pub fn solid_temperature(&self) -> &[f64] {
+        &self.solid_temperature
+    }
+
+    // Returns the level set tracking the liquid surface, if the solver simulates a liquid. With volume fractions it is
+    // the one rebuilt from them at the start of the last step
+    pub fn level_set(&self) -> Option<&LevelSet> {
+        self.liquid.as_ref()
+    }
+
+    // Returns the volume fractions tracking the liquid, if the solver uses them in place of the level set
+    pub fn volume_fraction(&self) -> Option<&VolumeFraction> {
+        self.volume_fraction.as_ref()
+    }
+
+    // Density of the fluid in each cell, which varies between the two fluids of a two phase flow
+    pub fn cell_density(&self) -> &[f64] {
+        &self.cell_density
+    }
+
+    // Returns the immersed filaments with their current points
+    pub fn filaments(&self) -> &[Filament] {
+        &self.filaments
+    }
+
+    // Returns the simulated time elapsed since the solver was created
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn add_inflow(&mut self, x: f64, y: f64, width: f64, height: f64, density: f64, u_velocity: f64, v_velocity: f64) {
         self.density.add_inflow(x, y, x + width, y + height, density);
         self.u_velocity.add_inflow(x, y, x + width, y + height, u_velocity);
         self.v_velocity.add_inflow(x, y, x + width, y + height, v_velocity);
     }
 
+    // Heats a region towards the given temperature without touching velocity
+    pub fn add_temperature_inflow(&mut self, x: f64, y: f64, width: f64, height: f64, temperature: f64) {
+        self.temperature.add_inflow(x, y, x + width, y + height, temperature);
+    }
+
+    // Injects a registered scalar over a region without touching velocity, use with add_inflow for coloured streams
+    pub fn add_scalar_inflow(&mut self, name: &str, x: f64, y: f64, width: f64, height: f64, value: f64) {
+        let index = self.scalar_index(name);
+        self.scalars[index].quantity.add_inflow(x, y, x + width, y + height, value);
+    }
+
+    // Cells crossed by the segments of every filament, sampled at half cell spacing
+    fn filament_cells(&self) -> Vec<usize> {
+        let mut cells = Vec::new();
+
+        for filament in &self.filaments {
+            for (start, end) in filament.segments() {
+                let samples = (2.0 * length(end.0 - start.0, end.1 - start.1) / self.cell_size).ceil() as usize + 1;
+
+                for sample in 0..=samples {
+                    let s = sample as f64 / samples as f64;
+                    let column = ((start.0 + s * (end.0 - start.0)) / self.cell_size).floor();
+                    let row = ((start.1 + s * (end.1 - start.1)) / self.cell_size).floor();
+
+                    if column >= 0.0 && row >= 0.0 && (column as usize) < self.columns && (row as usize) < self.rows {
+                        cells.push(row as usize * self.columns + column as usize);
+                    }
+                }
+            }
+        }
+
+        cells
+    }
+
     // Basic function to convert density_src array into an image buffer
-    pub fn to_image(&self, max_density: f64, buffer: &mut Vec<u8>) {
+    pub fn to_image(&self, max_density: f64, buffer: &mut [u8]) {
         for i in 0..(self.rows * self.columns) {
             let mut shade = ((max_density - self.density.src[i]) * 255.0 / max_density) as u8;
             shade = max(min(shade, 255), 0);
@@ -243,9 +1418,34 @@ impl FluidSolver {
                 shade = 0;
             }
 
-            buffer[i * 3 + 0] = shade;
+            buffer[i * 3] = shade;
             buffer[i * 3 + 1] = shade;
             buffer[i * 3 + 2] = shade;
         }
+
+        for i in self.filament_cells() {
+            buffer[i * 3..i * 3 + 3].copy_from_slice(&[0, 0, 0]);
+        }
     }
-}
\ No newline at end of file
+
+    // Converts three registered scalars into the red, green and blue channels of an image buffer
+    pub fn to_image_rgb(&self, channels: [&str; 3], max_value: f64, buffer: &mut [u8]) {
+        let fields: Vec<&FluidQuantity> = channels.iter().map(|name| self.scalar(name)).collect();
+
+        for i in 0..(self.rows * self.columns) {
+            for (channel, field) in fields.iter().enumerate() {
+                let mut shade = (clamp(field.src[i] / max_value, 0.0, 1.0) * 255.0) as u8;
+
+                if self.density.cell[i] == 1 {
+                    shade = 128;
+                }
+
+                buffer[i * 3 + channel] = shade;
+            }
+        }
+
+        for i in self.filament_cells() {
+            buffer[i * 3..i * 3 + 3].copy_from_slice(&[128, 128, 128]);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use crate::util::fluid_quantity::FluidQuantity;
+use crate::util::helper::clamp;
+
+pub enum BodyForce {
+    // Constant acceleration over the whole domain, such as gravity
+    Uniform(f64, f64),
+    // Acceleration as a function of position and time, returned as (x, y)
+    Field(Box<dyn Fn(f64, f64, f64) -> (f64, f64)>),
+    // Acceleration at each cell centre as row-major x and y grids, averaged onto the faces
+    Grid(Vec<f64>, Vec<f64>),
+    // Boussinesq buoyancy from gravity along +y, a density factor, an expansion factor and an ambient temperature.
+    // Smoke density adds weight and heat above ambient adds lift. It acts on the fluid only, never on bodies
+    Buoyancy(f64, f64, f64, f64)
+}
+
+impl BodyForce {
+    // Acceleration at a point, reading gridded forces from the cell containing it. Buoyancy depends on the fluid
+    // there and gives none
+    pub fn acceleration(&self, x: f64, y: f64, time: f64, columns: usize, cell_size: f64) -> (f64, f64) {
+        match self {
+            BodyForce::Uniform(x, y) => (*x, *y),
+            BodyForce::Field(f) => f(x, y, time),
+            BodyForce::Grid(x_grid, y_grid) => {
+                let rows = x_grid.len() / columns;
+                let column = clamp((x / cell_size).floor(), 0.0, (columns - 1) as f64) as usize;
+                let row = clamp((y / cell_size).floor(), 0.0, (rows - 1) as f64) as usize;
+
+                (x_grid[row * columns + column], y_grid[row * columns + column])
+            }
+            BodyForce::Buoyancy(_, _, _, _) => (0.0, 0.0)
+        }
+    }
+
+    // Integrates the acceleration over one timestep into the fluid faces of the velocity field. Buoyancy reads the
+    // density and temperature of the cells either side of each face
+    pub fn apply(&self, u_velocity: &mut FluidQuantity, v_velocity: &mut FluidQuantity, density: &FluidQuantity, temperature: &FluidQuantity, time: f64, timestep: f64) {
+        let cell_size = u_velocity.cell_size;
+
+        for row in 0..u_velocity.rows {
+            for column in 0..u_velocity.columns {
+                if u_velocity.cell_at(row, column) != 0 {
+                    continue;
+                }
+
+                let acceleration = match self {
+                    BodyForce::Uniform(x, _) => *x,
+                    BodyForce::Field(f) => {
+                        f((column as f64 + u_velocity.x_offset) * cell_size, (row as f64 + u_velocity.y_offset) * cell_size, time).0
+                    }
+                    BodyForce::Grid(x, _) => {
+                        let cells = u_velocity.columns - 1;
+                        let left = if column > 0 { column - 1 } else { column };
+                        let right = if column < cells { column } else { column - 1 };
+
+                        0.5 * (x[row * cells + left] + x[row * cells + right])
+                    }
+                    BodyForce::Buoyancy(_, _, _, _) => 0.0
+                };
+
+                *u_velocity.at_mut(row, column) += timestep * acceleration;
+            }
+        }
+
+        for row in 0..v_velocity.rows {
+            for column in 0..v_velocity.columns {
+                if v_velocity.cell_at(row, column) != 0 {
+                    continue;
+                }
+
+                let acceleration = match self {
+                    BodyForce::Uniform(_, y) => *y,
+                    BodyForce::Field(f) => {
+                        f((column as f64 + v_velocity.x_offset) * cell_size, (row as f64 + v_velocity.y_offset) * cell_size, time).1
+                    }
+                    BodyForce::Grid(_, y) => {
+                        let cells = v_velocity.rows - 1;
+                        let above = if row > 0 { row - 1 } else { row };
+                        let below = if row < cells { row } else { row - 1 };
+
+                        0.5 * (y[above * v_velocity.columns + column] + y[below * v_velocity.columns + column])
+                    }
+                    BodyForce::Buoyancy(gravity, density_factor, expansion_factor, ambient_temperature) => {
+                        let cells = v_velocity.rows - 1;
+                        let above = if row > 0 { row - 1 } else { row };
+                        let below = if row < cells { row } else { row - 1 };
+
+                        let face_density = 0.5 * (density.at(above, column) + density.at(below, column));
+                        let face_temperature = 0.5 * (temperature.at(above, column) + temperature.at(below, column));
+
+                        gravity * (density_factor * face_density - expansion_factor * (face_temperature - ambient_temperature))
+                    }
+                };
+
+                *v_velocity.at_mut(row, column) += timestep * acceleration;
+            }
+        }
+    }
+}
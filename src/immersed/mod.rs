@@ -0,0 +1,221 @@
+use crate::util::fluid_quantity::FluidQuantity;
+use crate::util::helper::{clamp, length};
+
+use std::f64::consts::PI;
+
+// Four point cosine kernel of Peskin, in units of cells
+fn kernel(r: f64) -> f64 {
+    if r.abs() >= 2.0 {
+        0.0
+    } else {
+        0.25 * (1.0 + (0.5 * PI * r).cos())
+    }
+}
+
+// Grid index and kernel weight of every node of a quantity within reach of a point. Periodic directions wrap, while
+// nodes beyond other edges or inside solids are skipped and the weights of the rest rescaled to sum to one
+fn stencil(x: f64, y: f64, quantity: &FluidQuantity) -> Vec<(usize, f64)> {
+    let mut nodes = Vec::with_capacity(16);
+
+    let grid_x = x / quantity.cell_size - quantity.x_offset;
+    let grid_y = y / quantity.cell_size - quantity.y_offset;
+
+    let first_column = grid_x.floor() as isize - 1;
+    let first_row = grid_y.floor() as isize - 1;
+
+    for row in first_row..(first_row + 4) {
+        if !quantity.periodic_y && (row < 0 || row >= quantity.rows as isize) {
+            continue;
+        }
+
+        let weight_y = kernel(grid_y - row as f64);
+
+        for column in first_column..(first_column + 4) {
+            if !quantity.periodic_x && (column < 0 || column >= quantity.columns as isize) {
+                continue;
+            }
+
+            let index = quantity.wrap_row(row) * quantity.columns + quantity.wrap_column(column);
+
+            if quantity.cell[index] == 0 {
+                nodes.push((index, weight_y * kernel(grid_x - column as f64)));
+            }
+        }
+    }
+
+    let total: f64 = nodes.iter().map(|&(_, weight)| weight).sum();
+
+    if total > 0.0 {
+        for (_, weight) in &mut nodes {
+            *weight /= total;
+        }
+    }
+
+    nodes
+}
+
+// Chain of Lagrangian points immersed in the fluid, resisting stretching and bending. Open chains model filaments
+// such as flags, closed ones elastic membranes. The structure is massless: its elastic forces are spread onto the
+// velocity faces and its points move with the interpolated fluid velocity
+pub struct Filament {
+    points: Vec<(f64, f64)>,
+    forces: Vec<(f64, f64)>,
+    anchors: Vec<(usize, f64, f64)>,
+    rest_length: f64,
+    stretching: f64,
+    bending: f64,
+    tether: f64,
+    closed: bool
+}
+
+impl Filament {
+    // Open filament of count evenly spaced points from start to end. It needs both ends, so count must be at least two
+    pub fn new_line(start: (f64, f64), end: (f64, f64), count: usize, stretching: f64, bending: f64) -> Self {
+        assert!(count >= 2, "A filament line needs at least two points, got {}", count);
+
+        let points: Vec<(f64, f64)> = (0..count).map(|i| {
+            let s = i as f64 / (count - 1) as f64;
+            (start.0 + s * (end.0 - start.0), start.1 + s * (end.1 - start.1))
+        }).collect();
+
+        Filament {
+            forces: vec![(0.0, 0.0); count],
+            anchors: Vec::new(),
+            rest_length: length(end.0 - start.0, end.1 - start.1) / (count - 1) as f64,
+            stretching,
+            bending,
+            tether: stretching,
+            closed: false,
+            points
+        }
+    }
+
+    // Closed membrane of count points around a circle
+    pub fn new_ring(centre_x: f64, centre_y: f64, radius: f64, count: usize, stretching: f64, bending: f64) -> Self {
+        let points: Vec<(f64, f64)> = (0..count).map(|i| {
+            let angle = 2.0 * PI * i as f64 / count as f64;
+            (centre_x + radius * angle.cos(), centre_y + radius * angle.sin())
+        }).collect();
+
+        Filament {
+            forces: vec![(0.0, 0.0); count],
+            anchors: Vec::new(),
+            rest_length: 2.0 * radius * (PI / count as f64).sin(),
+            stretching,
+            bending,
+            tether: stretching,
+            closed: true,
+            points
+        }
+    }
+
+    // Ties a point to where it currently is with a stiff spring. Pinning the first two points of a filament clamps it
+    pub fn pin(mut self, index: usize) -> Self {
+        let (x, y) = self.points[index];
+        self.anchors.push((index, x, y));
+        self
+    }
+
+    // Sets the stiffness of the springs holding pinned points, which defaults to the stretching stiffness
+    pub fn tether_stiffness(mut self, stiffness: f64) -> Self {
+        self.tether = stiffness;
+        self
+    }
+
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.points
+    }
+
+    // Pairs of consecutive points, including the one closing a membrane
+    pub fn segments(&self) -> Vec<((f64, f64), (f64, f64))> {
+        let count = self.points.len();
+        let segments = if self.closed { count } else { count - 1 };
+
+        (0..segments).map(|segment| (self.points[segment], self.points[(segment + 1) % count])).collect()
+    }
+
+    // Elastic force on every point from stretching each segment, bending each pair of segments and the tethers
+    fn compute_forces(&mut self) {
+        let count = self.points.len();
+        let segments = if self.closed { count } else { count - 1 };
+
+        for force in &mut self.forces {
+            *force = (0.0, 0.0);
+        }
+
+        for segment in 0..segments {
+            let next = (segment + 1) % count;
+            let dx = self.points[next].0 - self.points[segment].0;
+            let dy = self.points[next].1 - self.points[segment].1;
+            let distance = length(dx, dy).max(1e-12);
+
+            let tension = self.stretching * (distance / self.rest_length - 1.0);
+
+            self.forces[segment].0 += tension * dx / distance;
+            self.forces[segment].1 += tension * dy / distance;
+            self.forces[next].0 -= tension * dx / distance;
+            self.forces[next].1 -= tension * dy / distance;
+        }
+
+        // Bending energy is half the stiffness times the squared second difference over the cubed spacing
+        let stiffness = self.bending / self.rest_length.powi(3);
+        let bends = if self.closed { 0..count } else { 1..(count - 1) };
+
+        for point in bends {
+            let before = (point + count - 1) % count;
+            let after = (point + 1) % count;
+
+            let curvature_x = self.points[after].0 - 2.0 * self.points[point].0 + self.points[before].0;
+            let curvature_y = self.points[after].1 - 2.0 * self.points[point].1 + self.points[before].1;
+
+            self.forces[before].0 -= stiffness * curvature_x;
+            self.forces[before].1 -= stiffness * curvature_y;
+            self.forces[point].0 += 2.0 * stiffness * curvature_x;
+            self.forces[point].1 += 2.0 * stiffness * curvature_y;
+            self.forces[after].0 -= stiffness * curvature_x;
+            self.forces[after].1 -= stiffness * curvature_y;
+        }
+
+        for &(index, x, y) in &self.anchors {
+            self.forces[index].0 += self.tether * (x - self.points[index].0);
+            self.forces[index].1 += self.tether * (y - self.points[index].1);
+        }
+    }
+
+    // Spreads the elastic forces onto the velocity faces as a force density, accelerating the fluid over one timestep.
+    // Each point accelerates the fluid by the density of the cell it lies in, given row by row in density
+    pub fn spread(&mut self, u_velocity: &mut FluidQuantity, v_velocity: &mut FluidQuantity, density: &[f64], timestep: f64) {
+        self.compute_forces();
+
+        let cell_size = u_velocity.cell_size;
+        let (rows, columns) = (u_velocity.rows, v_velocity.columns);
+
+        for (&(x, y), &(force_x, force_y)) in self.points.iter().zip(&self.forces) {
+            let row = clamp((y / cell_size).floor(), 0.0, (rows - 1) as f64) as usize;
+            let column = clamp((x / cell_size).floor(), 0.0, (columns - 1) as f64) as usize;
+            let scale = timestep / (density[row * columns + column] * cell_size * cell_size);
+
+            for (index, weight) in stencil(x, y, u_velocity) {
+                u_velocity.src[index] += scale * weight * force_x;
+            }
+
+            for (index, weight) in stencil(x, y, v_velocity) {
+                v_velocity.src[index] += scale * weight * force_y;
+            }
+        }
+
+        u_velocity.copy_periodic_faces();
+        v_velocity.copy_periodic_faces();
+    }
+
+    // Moves every point with the fluid velocity interpolated through the same kernel used to spread the forces
+    pub fn advance(&mut self, u_velocity: &FluidQuantity, v_velocity: &FluidQuantity, timestep: f64) {
+        for point in &mut self.points {
+            let velocity_x: f64 = stencil(point.0, point.1, u_velocity).iter().map(|&(index, weight)| weight * u_velocity.src[index]).sum();
+            let velocity_y: f64 = stencil(point.0, point.1, v_velocity).iter().map(|&(index, weight)| weight * v_velocity.src[index]).sum();
+
+            point.0 += timestep * velocity_x;
+            point.1 += timestep * velocity_y;
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use crate::boundary::SolidBody;
+use crate::util::extrapolation::extrapolate;
+use crate::util::fluid_quantity::FluidQuantity;
+use crate::util::helper::{clamp, length, max, min};
+
+// Fraction of the way from a liquid sample to a neighbouring air sample at which the surface crosses, kept away from
+// zero so that the ghost fluid coefficients stay bounded
+pub fn crossing(inside: f64, outside: f64) -> f64 {
+    clamp(inside / (inside - outside), 0.01, 1.0)
+}
+
+// Signed distance to a liquid surface sampled at cell centres, negative inside the liquid. It is carried with the flow
+// alongside the other scalars and redistanced every step, so the surface stays sharp while the field stays smooth
+pub struct LevelSet {
+    pub distance: FluidQuantity
+}
+
+impl LevelSet {
+    // Creates a domain filled with air. Liquid is added with add_region
+    pub fn new(rows: usize, columns: usize, cell_size: f64) -> Self {
+        let mut distance = FluidQuantity::new(rows, columns, 0.5, 0.5, cell_size);
+        let far = (rows + columns) as f64 * cell_size;
+
+        for value in &mut distance.src {
+            *value = far;
+        }
+
+        LevelSet { distance }
+    }
+
+    // Fills the inside of a shape with liquid, merging it with the liquid already present
+    pub fn add_region(mut self, body: &SolidBody) -> Self {
+        let cell_size = self.distance.cell_size;
+
+        for row in 0..self.distance.rows {
+            for column in 0..self.distance.columns {
+                let distance = body.distance((column as f64 + 0.5) * cell_size, (row as f64 + 0.5) * cell_size);
+                let value = self.distance.at_mut(row, column);
+                *value = min(*value, distance);
+            }
+        }
+
+        self
+    }
+
+    pub fn at(&self, row: usize, column: usize) -> f64 {
+        self.distance.at(row, column)
+    }
+
+    // Returns true where the centre of the cell lies in the liquid
+    pub fn is_liquid(&self, row: usize, column: usize) -> bool {
+        self.distance.at(row, column) < 0.0
+    }
+
+    // Curvature of the surface through the cell centre, the divergence of the unit normal pointing out of the liquid,
+    // from central differences of the distance field. It is positive where the liquid bulges outward and is limited to
+    // what the grid can resolve
+    pub fn curvature(&self, row: usize, column: usize) -> f64 {
+        let cell_size = self.distance.cell_size;
+        let (row, column) = (row as isize, column as isize);
+        let at = |row_offset: isize, column_offset: isize| self.distance.wrapped_at(row + row_offset, column + column_offset);
+
+        let dx = (at(0, 1) - at(0, -1)) / (2.0 * cell_size);
+        let dy = (at(1, 0) - at(-1, 0)) / (2.0 * cell_size);
+        let dxx = (at(0, 1) - 2.0 * at(0, 0) + at(0, -1)) / (cell_size * cell_size);
+        let dyy = (at(1, 0) - 2.0 * at(0, 0) + at(-1, 0)) / (cell_size * cell_size);
+        let dxy = (at(1, 1) - at(1, -1) - at(-1, 1) + at(-1, -1)) / (4.0 * cell_size * cell_size);
+
+        let gradient = length(dx, dy);
+
+        if gradient < 1e-12 {
+            return 0.0;
+        }
+
+        let curvature = (dxx * dy * dy - 2.0 * dx * dy * dxy + dyy * dx * dx) / (gradient * gradient * gradient);
+        clamp(curvature, -1.0 / cell_size, 1.0 / cell_size)
+    }
+
+    // Curvature where the surface crosses between a liquid cell and a neighbouring air cell, interpolated from the
+    // curvature at the two cell centres
+    pub fn surface_curvature(&self, liquid: usize, air: usize) -> f64 {
+        let columns = self.distance.columns;
+        let fraction = crossing(self.distance.src[liquid], self.distance.src[air]);
+
+        (1.0 - fraction) * self.curvature(liquid / columns, liquid % columns) + fraction * self.curvature(air / columns, air % columns)
+    }
+
+    // Fraction of the cell filled with liquid, smoothing the surface over one cell
+    pub fn fraction(&self, row: usize, column: usize) -> f64 {
+        clamp(0.5 - self.distance.at(row, column) / self.distance.cell_size, 0.0, 1.0)
+    }
+
+    // Area of liquid in the domain
+    pub fn volume(&self) -> f64 {
+        let cell_size = self.distance.cell_size;
+        let columns = self.distance.columns;
+
+        (0..self.distance.src.len()).map(|element| self.fraction(element / columns, element % columns)).sum::<f64>() * cell_size * cell_size
+    }
+
+    // Restores the signed distance property after advection has stretched the field. Cells next to the surface keep
+    // the surface in place, and the rest are solved outward from them with fast sweeping of the eikonal equation in the
+    // four diagonal directions
+    pub fn redistance(&mut self) {
+        let rows = self.distance.rows;
+        let columns = self.distance.columns;
+        let cell_size = self.distance.cell_size;
+        let far = (rows + columns) as f64 * cell_size;
+
+        let values = self.distance.src.clone();
+        let mut unsigned = vec![far; rows * columns];
+        let mut fixed = vec![false; rows * columns];
+
+        for row in 0..rows {
+            for column in 0..columns {
+                let element = row * columns + column;
+                let value = values[element];
+
+                let left = if column > 0 { element - 1 } else { element };
+                let right = if column < columns - 1 { element + 1 } else { element };
+                let above = if row > 0 { element - columns } else { element };
+                let below = if row < rows - 1 { element + columns } else { element };
+
+                let crossed = [left, right, above, below].iter().any(|&neighbour| (values[neighbour] < 0.0) != (value < 0.0));
+
+                if !crossed {
+                    continue;
+                }
+
+                // Values next to the surface are kept as they are, since the curvature for surface tension is sensitive
+                // to any change in them. Only where advection has badly stretched the field are they divided by the
+                // gradient, which corrects the distance without moving the surface
+                let gradient_x = (values[right] - values[left]) / (max(right - left, 1) as f64 * cell_size);
+                let gradient_y = (values[below] - values[above]) / (max((below - above) / columns, 1) as f64 * cell_size);
+                let gradient = length(gradient_x, gradient_y);
+                let stretched = !(0.5..=2.0).contains(&gradient);
+
+                unsigned[element] = min(if stretched { value.abs() / max(gradient, 1e-12) } else { value.abs() }, cell_size);
+                fixed[element] = true;
+            }
+        }
+
+        let sweeps: [(bool, bool); 4] = [(false, false), (true, false), (false, true), (true, true)];
+
+        for &(reverse_rows, reverse_columns) in &sweeps {
+            for row_step in 0..rows {
+                let row = if reverse_rows { rows - 1 - row_step } else { row_step };
+
+                for column_step in 0..columns {
+                    let column = if reverse_columns { columns - 1 - column_step } else { column_step };
+                    let element = row * columns + column;
+
+                    if fixed[element] {
+                        continue;
+                    }
+
+                    let a = min(if column > 0 { unsigned[element - 1] } else { far }, if column < columns - 1 { unsigned[element + 1] } else { far });
+                    let b = min(if row > 0 { unsigned[element - columns] } else { far }, if row < rows - 1 { unsigned[element + columns] } else { far });
+
+                    let candidate = if (a - b).abs() >= cell_size {
+                        min(a, b) + cell_size
+                    } else {
+                        0.5 * (a + b + (2.0 * cell_size * cell_size - (a - b) * (a - b)).sqrt())
+                    };
+
+                    unsigned[element] = min(unsigned[element], candidate);
+                }
+            }
+        }
+
+        for (element, value) in self.distance.src.iter_mut().enumerate() {
+            *value = if values[element] < 0.0 { -unsigned[element] } else { unsigned[element] };
+        }
+    }
+
+    // Extends velocity from faces touching liquid cells out into the air, so that advection near the surface reads
+    // the motion of the liquid. Faces inside solids are left alone since the body sets them
+    pub fn extrapolate_velocity(&self, velocity: &mut FluidQuantity, horizontal: bool, layers: usize) {
+        let (rows, columns) = (self.distance.rows, self.distance.columns);
+        let mut known = vec![false; velocity.rows * velocity.columns];
+        let mut depth = vec![0.0; velocity.rows * velocity.columns];
+
+        for row in 0..velocity.rows {
+            for column in 0..velocity.columns {
+                // Cells on either side of the face, clamped at the domain edges
+                let (first, second) = if horizontal {
+                    ((row, column.saturating_sub(1)), (row, min(column, columns - 1)))
+                } else {
+                    ((row.saturating_sub(1), column), (min(row, rows - 1), column))
+                };
+
+                let element = row * velocity.columns + column;
+                let (first, second) = (self.at(first.0, first.1), self.at(second.0, second.1));
+
+                known[element] = velocity.cell[element] != 0 || first < 0.0 || second < 0.0;
+                depth[element] = 0.5 * (first + second);
+            }
+        }
+
+        let period_x = velocity.periodic_x.then(|| velocity.period_x());
+        let period_y = velocity.periodic_y.then(|| velocity.period_y());
+
+        extrapolate(&mut velocity.src, &known, &depth, velocity.rows, velocity.columns, period_x, period_y, layers as f64 * velocity.cell_size);
+    }
+}
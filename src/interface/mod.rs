@@ -0,0 +1,5 @@
+mod level_set;
+mod vof;
+
+pub use self::level_set::{crossing, LevelSet};
+pub use self::vof::VolumeFraction;
@@ -26,50 +26,67 @@ pub enum Interpolation {
     BiCubic
 }
 
-impl Interpolation {
-    pub fn run(&self, mut x: f64, mut y: f64, field: &FluidQuantity) -> f64 {
-        match self {
-            Interpolation::BiLinear => {
-                x = clamp(x - field.x_offset, 0.0, field.columns as f64 - 1.001);
-                y = clamp(y - field.y_offset, 0.0, field.rows as f64 - 1.001);
+// Shifts a sample position into grid coordinates, clamping it inside the grid in non-periodic directions
+fn grid_position(x: f64, y: f64, field: &FluidQuantity) -> (f64, f64) {
+    let mut x = x - field.x_offset;
+    let mut y = y - field.y_offset;
 
-                let p1_x = x.trunc() as usize;
-                let p1_y = y.trunc() as usize;
+    if !field.periodic_x {
+        x = clamp(x, 0.0, field.columns as f64 - 1.001);
+    }
 
-                let p1 = field.at(p1_y, p1_x);
-                let p2 = field.at(p1_y, p1_x + 1);
-                let p3 = field.at(p1_y + 1, p1_x);
-                let p4 = field.at(p1_y + 1, p1_x + 1);
+    if !field.periodic_y {
+        y = clamp(y, 0.0, field.rows as f64 - 1.001);
+    }
 
-                let l1 = linear_interpolate(p1, p2, x.fract());
-                let l2 = linear_interpolate(p3, p4, x.fract());
+    (x, y)
+}
 
-                linear_interpolate(l1, l2, y.fract())
-            },
-            Interpolation::BiCubic => {
-                x = clamp(x - field.x_offset, 0.0, field.columns as f64 - 1.001);
-                y = clamp(y - field.y_offset, 0.0, field.rows as f64 - 1.001);
+impl Interpolation {
+    pub fn run(&self, x: f64, y: f64, field: &FluidQuantity) -> f64 {
+        let (x, y) = grid_position(x, y, field);
+
+        let p1_x = x.floor() as isize;
+        let p1_y = y.floor() as isize;
 
-                let p1_x = x.trunc() as usize;
-                let p1_y = y.trunc() as usize;
+        let fract_x = x - x.floor();
+        let fract_y = y - y.floor();
 
-                let x0 = max(p1_x as isize - 1, 0) as usize;
-                let x1 = p1_x;
-                let x2 = p1_x + 1;
-                let x3 = min(p1_x + 2, field.columns - 1);
+        match self {
+            Interpolation::BiLinear => {
+                let x1 = field.wrap_column(p1_x);
+                let x2 = field.wrap_column(p1_x + 1);
+                let y1 = field.wrap_row(p1_y);
+                let y2 = field.wrap_row(p1_y + 1);
 
-                let y0 = max(p1_y as isize - 1, 0) as usize;
-                let y1 = p1_y;
-                let y2 = p1_y + 1;
-                let y3 = min(p1_y + 2, field.rows - 1);
+                let p1 = field.at(y1, x1);
+                let p2 = field.at(y1, x2);
+                let p3 = field.at(y2, x1);
+                let p4 = field.at(y2, x2);
 
-                let q0 = cubic_interpolate(field.at(y0, x0), field.at(y0, x1), field.at(y0, x2), field.at(y0, x3), x);
-                let q1 = cubic_interpolate(field.at(y1, x0), field.at(y1, x1), field.at(y1, x2), field.at(y1, x3), x);
-                let q2 = cubic_interpolate(field.at(y2, x0), field.at(y2, x1), field.at(y2, x2), field.at(y2, x3), x);
-                let q3 = cubic_interpolate(field.at(y3, x0), field.at(y3, x1), field.at(y3, x2), field.at(y3, x3), x);
+                let l1 = linear_interpolate(p1, p2, fract_x);
+                let l2 = linear_interpolate(p3, p4, fract_x);
 
-                cubic_interpolate(q0, q1, q2, q3, y)
+                linear_interpolate(l1, l2, fract_y)
+            },
+            Interpolation::BiCubic => {
+                let x0 = field.wrap_column(p1_x - 1);
+                let x1 = field.wrap_column(p1_x);
+                let x2 = field.wrap_column(p1_x + 1);
+                let x3 = field.wrap_column(p1_x + 2);
+
+                let y0 = field.wrap_row(p1_y - 1);
+                let y1 = field.wrap_row(p1_y);
+                let y2 = field.wrap_row(p1_y + 1);
+                let y3 = field.wrap_row(p1_y + 2);
+
+                let q0 = cubic_interpolate(field.at(y0, x0), field.at(y0, x1), field.at(y0, x2), field.at(y0, x3), fract_x);
+                let q1 = cubic_interpolate(field.at(y1, x0), field.at(y1, x1), field.at(y1, x2), field.at(y1, x3), fract_x);
+                let q2 = cubic_interpolate(field.at(y2, x0), field.at(y2, x1), field.at(y2, x2), field.at(y2, x3), fract_x);
+                let q3 = cubic_interpolate(field.at(y3, x0), field.at(y3, x1), field.at(y3, x2), field.at(y3, x3), fract_x);
+
+                cubic_interpolate(q0, q1, q2, q3, fract_y)
             },
         }
     }
-}
\ No newline at end of file
+}
@@ -1,6 +1,10 @@
 pub mod advection;
+pub mod diffusion;
 pub mod fluid_solver;
+pub mod forces;
+pub mod immersed;
 pub mod integration;
+pub mod interface;
 pub mod interpolation;
 pub mod linear_solvers;
 pub mod util;
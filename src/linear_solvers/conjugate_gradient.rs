@@ -1,36 +1,7 @@
 use crate::util::sparse::Sparse;
 use crate::util::linear_algebra::{infinity_norm, dot_product, matrix_vector_product, scaled_add1, scaled_add2};
-use crate::util::fluid_quantity::FluidQuantity;
 
-fn build_pressure_matrix(a: &mut Sparse, cell: &Vec<u8>, fluid_density: f64, dt: f64, dx: f64, rows: usize, columns: usize, u_velocity: &FluidQuantity, v_velocity: &FluidQuantity) {
-    let scale = dt / (fluid_density * dx * dx);
-    a.diagonals = vec![0.0; rows * columns];
-    a.plus_x = vec![0.0; rows * columns];
-    a.plus_y = vec![0.0; rows * columns];
-
-    for row in 0..rows {
-        for column in 0..columns {
-            let element = row * columns + column;
-            if cell[element] == 0 {
-                if column < columns - 1 && cell[element + 1] == 0 {
-                    let factor = scale * u_velocity.volume_at(row, column + 1);
-                    a.diagonals[element] += factor;
-                    a.diagonals[element + 1] += factor;
-                    a.plus_x[element] = -factor;
-                }
-
-                if row < rows - 1 && cell[element + columns] == 0 {
-                    let factor = scale * v_velocity.volume_at(row + 1, column);
-                    a.diagonals[element] += factor;
-                    a.diagonals[element + columns] += factor;
-                    a.plus_y[element] = -factor;
-                }
-            }
-        }
-    }
-}
-
-fn build_preconditioner(preconditioner: &mut Vec<f64>, a: &Sparse, cell: &Vec<u8>, rows: usize, columns: usize) {
+fn build_preconditioner(preconditioner: &mut [f64], a: &Sparse, cell: &[u8], rows: usize, columns: usize) {
     let tau = 0.97;
 
     for row in 0..rows {
@@ -58,7 +29,7 @@ fn build_preconditioner(preconditioner: &mut Vec<f64>, a: &Sparse, cell: &Vec<u8
     }
 }
 
-fn apply_preconditioner(auxiliary: &mut Vec<f64>, residual: &Vec<f64>, a: &Sparse, preconditioner: &Vec<f64>, cell: &Vec<u8>, rows: usize, columns: usize) {
+fn apply_preconditioner(auxiliary: &mut [f64], residual: &[f64], a: &Sparse, preconditioner: &[f64], cell: &[u8], rows: usize, columns: usize) {
     for row in 0..rows {
         for column in 0..columns {
             let element = row * columns + column;
@@ -100,29 +71,25 @@ fn apply_preconditioner(auxiliary: &mut Vec<f64>, residual: &Vec<f64>, a: &Spars
     }
 }
 
-pub fn conjugate_gradient(pressure: &mut Vec<f64>,
-                          residual: &mut Vec<f64>,
-                          auxiliary: &mut Vec<f64>,
-                          search: &mut Vec<f64>,
-                          preconditioner: &mut Vec<f64>,
-                          a: &mut Sparse,
-                          cell: &Vec<u8>,
-                          fluid_density: f64,
-                          dt: f64,
-                          dx: f64,
+// Solves a x = b over the cells marked 0, overwriting b with the final residual
+#[allow(clippy::too_many_arguments)]
+pub fn conjugate_gradient(x: &mut [f64],
+                          residual: &mut [f64],
+                          auxiliary: &mut [f64],
+                          search: &mut [f64],
+                          preconditioner: &mut [f64],
+                          a: &Sparse,
+                          cell: &[u8],
                           rows: usize,
                           columns: usize,
-                          limit: usize,
-                          u_velocity: &FluidQuantity,
-                          v_velocity: &FluidQuantity) {
+                          limit: usize) {
 
-    build_pressure_matrix(a, cell, fluid_density, dt, dx, rows, columns, u_velocity, v_velocity);
     build_preconditioner(preconditioner, a, cell, rows, columns);
 
-    *pressure = vec![0.0; rows * columns];
+    x.iter_mut().for_each(|p| *p = 0.0);
 
     apply_preconditioner(auxiliary, residual, a, preconditioner, cell, rows, columns);
-    *search = auxiliary.clone();
+    search.copy_from_slice(auxiliary);
 
     let mut max_error = infinity_norm(residual);
 
@@ -137,7 +104,7 @@ pub fn conjugate_gradient(pressure: &mut Vec<f64>,
 
         let alpha = sigma / dot_product(auxiliary, search);
 
-        scaled_add1(pressure, search, alpha);
+        scaled_add1(x, search, alpha);
         scaled_add1(residual, auxiliary, -alpha);
 
         max_error = infinity_norm(residual);
@@ -1,5 +1,7 @@
-pub fn gauss_siedel(pressure: &mut Vec<f64>, residual: &mut Vec<f64>, fluid_density: f64, dt: f64, dx: f64, rows: usize, columns: usize, limit: usize) {
-    let scale = dt / (fluid_density * dx * dx);
+use crate::util::sparse::Sparse;
+
+// Solves a x = b over the cells marked 0, starting from the current contents of x
+pub fn gauss_siedel(x: &mut [f64], b: &[f64], a: &Sparse, cell: &[u8], rows: usize, columns: usize, limit: usize) {
     let mut max_delta = 0.0;
 
     for iteration in 0..limit {
@@ -7,37 +9,21 @@ pub fn gauss_siedel(pressure: &mut Vec<f64>, residual: &mut Vec<f64>, fluid_dens
 
         for row in 0..rows {
             for column in 0..columns {
-                let mut diagonal = 0.0;
-                let mut off_diagonal = 0.0;
                 let element = row * columns + column;
 
-                if column > 0 {
-                    diagonal += scale;
-                    off_diagonal -= scale * pressure[element - 1];
-                }
-
-                if row > 0 {
-                    diagonal += scale;
-                    off_diagonal -= scale * pressure[element - columns];
+                if cell[element] != 0 || a.diagonals[element] == 0.0 {
+                    continue;
                 }
 
-                if column < (columns - 1) {
-                    diagonal += scale;
-                    off_diagonal -= scale * pressure[element + 1];
-                }
-
-                if row < (rows - 1) {
-                    diagonal += scale;
-                    off_diagonal -= scale * pressure[element + columns];
-                }
+                let off_diagonal = a.off_diagonal_product(x, row, column, rows, columns);
 
-                let new_pressure = (residual[element] - off_diagonal) / diagonal;
-                let delta = (pressure[element] - new_pressure).abs();
+                let new_value = (b[element] - off_diagonal) / a.diagonals[element];
+                let delta = (x[element] - new_value).abs();
                 if max_delta < delta {
                     max_delta = delta;
                 };
 
-                pressure[element] = new_pressure;
+                x[element] = new_value;
             }
         }
 
@@ -47,4 +33,4 @@ pub fn gauss_siedel(pressure: &mut Vec<f64>, residual: &mut Vec<f64>, fluid_dens
         }
     }
     println!("Exceeded budget of {} iterations, maximum change was {}", limit, max_delta);
-}
\ No newline at end of file
+}
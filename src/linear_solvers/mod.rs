@@ -5,7 +5,6 @@ mod conjugate_gradient;
 
 pub use self::gauss_siedel::*;
 pub use self::conjugate_gradient::*;
-use crate::util::fluid_quantity::FluidQuantity;
 
 pub enum LinearSolver {
     GaussSiedel,
@@ -13,29 +12,26 @@ pub enum LinearSolver {
 }
 
 impl LinearSolver {
+    // Solves a x = b for the cells marked 0 in cell. The contents of b are not preserved
+    #[allow(clippy::too_many_arguments)]
     pub fn solve(&mut self,
-                 pressure: &mut Vec<f64>,
-                 residual: &mut Vec<f64>,
-                 auxiliary: &mut Vec<f64>,
-                 search: &mut Vec<f64>,
-                 preconditioner: &mut Vec<f64>,
-                 a: &mut Sparse,
-                 cell: &Vec<u8>,
-                 fluid_density: f64,
-                 timestep: f64,
-                 cell_size: f64,
+                 x: &mut [f64],
+                 b: &mut [f64],
+                 auxiliary: &mut [f64],
+                 search: &mut [f64],
+                 preconditioner: &mut [f64],
+                 a: &Sparse,
+                 cell: &[u8],
                  rows: usize,
                  columns: usize,
-                 iterations: usize,
-                 u_velocity: &FluidQuantity,
-                 v_velocity: &FluidQuantity) {
+                 iterations: usize) {
         match self {
             LinearSolver::GaussSiedel => {
-                gauss_siedel(pressure, residual, fluid_density, timestep, cell_size, rows, columns, iterations)
+                gauss_siedel(x, b, a, cell, rows, columns, iterations)
             }
             LinearSolver::ConjugateGradient => {
-                conjugate_gradient(pressure, residual, auxiliary, search, preconditioner, a, cell, fluid_density, timestep, cell_size, rows, columns, iterations, u_velocity, v_velocity)
+                conjugate_gradient(x, b, auxiliary, search, preconditioner, a, cell, rows, columns, iterations)
             }
         }
     }
-}
\ No newline at end of file
+}
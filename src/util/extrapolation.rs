@@ -0,0 +1,142 @@
+// Extends a grid field from the cells where it is known into neighbouring unknown cells, holding it constant along the
+// gradient of a distance field. Unknown cells are visited in order of increasing depth, the distance from the known
+// region, as in fast marching, so every cell is finished once the cells it draws from are. Each takes the average of
+// its finished neighbours weighted by how much shallower they are, which follows the characteristics of the distance
+// field instead of the grid axes. Cells deeper than max_depth are left untouched. Neighbours wrap around in the
+// directions given a period, the number of distinct rows or columns as in FluidQuantity::period_x and period_y
+#[allow(clippy::too_many_arguments)]
+pub fn extrapolate(values: &mut [f64], known: &[bool], depth: &[f64], rows: usize, columns: usize, period_x: Option<usize>, period_y: Option<usize>, max_depth: f64) {
+    let mut done = known.to_vec();
+    let mut pending: Vec<usize> = (0..rows * columns).filter(|&element| !known[element] && depth[element] <= max_depth).collect();
+
+    pending.sort_by(|&first, &second| depth[first].partial_cmp(&depth[second]).unwrap_or(std::cmp::Ordering::Equal));
+
+    // Cells whose depth disagrees with their neighbours, as happens where two surfaces meet, may have no shallower
+    // finished neighbour on the first pass. They fall back to the plain average and are retried until none are left
+    while !pending.is_empty() {
+        let mut remaining = Vec::new();
+
+        for &element in &pending {
+            let row = element / columns;
+            let column = element % columns;
+
+            let neighbours = [
+                step(column, -1, columns, period_x).map(|column| row * columns + column),
+                step(column, 1, columns, period_x).map(|column| row * columns + column),
+                step(row, -1, rows, period_y).map(|row| row * columns + column),
+                step(row, 1, rows, period_y).map(|row| row * columns + column)
+            ];
+
+            let (mut total, mut weights, mut plain, mut count) = (0.0, 0.0, 0.0, 0.0);
+
+            for &neighbour in neighbours.iter().flatten() {
+                if done[neighbour] {
+                    let weight = depth[element] - depth[neighbour];
+
+                    if weight > 0.0 {
+                        total += weight * values[neighbour];
+                        weights += weight;
+                    }
+
+                    plain += values[neighbour];
+                    count += 1.0;
+                }
+            }
+
+            if weights > 0.0 {
+                values[element] = total / weights;
+            } else if count > 0.0 {
+                values[element] = plain / count;
+            } else {
+                remaining.push(element);
+                continue;
+            }
+
+            done[element] = true;
+        }
+
+        // Cells with no path to a known cell can never be reached
+        if remaining.len() == pending.len() {
+            break;
+        }
+
+        pending = remaining;
+    }
+}
+
+// Index one step along an axis of the given length, wrapping with the period if there is one and None past an edge
+fn step(position: usize, offset: isize, count: usize, period: Option<usize>) -> Option<usize> {
+    let position = position as isize + offset;
+
+    match period {
+        Some(period) => Some(position.rem_euclid(period as isize) as usize),
+        None if position >= 0 && position < count as isize => Some(position as usize),
+        None => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_held_constant_along_the_distance_gradient() {
+        let (rows, columns) = (3, 5);
+        let mut values: Vec<f64> = (0..rows * columns).map(|element| if element % columns == 0 { element as f64 } else { -1.0 }).collect();
+        let known: Vec<bool> = (0..rows * columns).map(|element| element % columns == 0).collect();
+        let depth: Vec<f64> = (0..rows * columns).map(|element| (element % columns) as f64).collect();
+
+        extrapolate(&mut values, &known, &depth, rows, columns, None, None, 10.0);
+
+        for (element, &value) in values.iter().enumerate() {
+            assert_eq!(value, (element - element % columns) as f64);
+        }
+    }
+
+    #[test]
+    fn cells_deeper_than_the_limit_are_untouched() {
+        let mut values = vec![3.0, 0.0, 0.0, 0.0];
+        let known = [true, false, false, false];
+        let depth = [0.0, 1.0, 2.0, 3.0];
+
+        extrapolate(&mut values, &known, &depth, 1, 4, None, None, 2.0);
+
+        assert_eq!(values, vec![3.0, 3.0, 3.0, 0.0]);
+    }
+
+    #[test]
+    fn neighbours_wrap_across_a_periodic_edge() {
+        let known = [true, false, true, false];
+        let depth = [0.0, 1.0, 0.0, 1.0];
+
+        let mut values = vec![0.0, -1.0, 4.0, -1.0];
+        extrapolate(&mut values, &known, &depth, 1, 4, None, None, 10.0);
+        assert_eq!(values, vec![0.0, 2.0, 4.0, 4.0]);
+
+        let mut values = vec![0.0, -1.0, 4.0, -1.0];
+        extrapolate(&mut values, &known, &depth, 1, 4, Some(4), None, 10.0);
+        assert_eq!(values, vec![0.0, 2.0, 4.0, 2.0]);
+
+        let mut values = vec![0.0, -1.0, 4.0, -1.0];
+        extrapolate(&mut values, &known, &depth, 4, 1, None, Some(4), 10.0);
+        assert_eq!(values, vec![0.0, 2.0, 4.0, 2.0]);
+    }
+
+    #[test]
+    fn unreachable_cells_are_untouched() {
+        let mut values = vec![1.0, 2.0, 3.0];
+
+        extrapolate(&mut values, &[false; 3], &[1.0; 3], 1, 3, None, None, 10.0);
+
+        assert_eq!(values, vec![1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn steps_wrap_only_with_a_period() {
+        assert_eq!(step(0, -1, 4, None), None);
+        assert_eq!(step(3, 1, 4, None), None);
+        assert_eq!(step(2, 1, 4, None), Some(3));
+        assert_eq!(step(0, -1, 4, Some(4)), Some(3));
+        assert_eq!(step(3, 1, 5, Some(4)), Some(0));
+    }
+}
@@ -1,7 +1,11 @@
-use crate::util::helper::{cubic_pulse, length, max, min};
+use crate::util::helper::{clamp, cubic_pulse, length, max, min};
 use std::mem::swap;
-use crate::boundary::SolidBody;
+use crate::boundary::{BodyGrid, SolidBody};
 use crate::util::occupancy::occupancy;
+use crate::util::extrapolation::extrapolate;
+
+// Body id of points with no body nearby, which must never be used to look a body up
+pub const NO_BODY: u32 = u32::MAX;
 
 pub struct FluidQuantity {
     pub src: Vec<f64>,
@@ -11,13 +15,14 @@ pub struct FluidQuantity {
     pub phi:       Vec<f64>,
     pub volume:    Vec<f64>,
     pub cell: Vec<u8>,
-    pub body: Vec<u8>,
-    pub mask: Vec<u8>,
+    pub body: Vec<u32>,
     pub rows:      usize,
     pub columns:   usize,
     pub x_offset:  f64,
     pub y_offset:  f64,
     pub cell_size: f64,
+    pub periodic_x: bool,
+    pub periodic_y: bool,
 }
 
 impl FluidQuantity {
@@ -28,15 +33,16 @@ impl FluidQuantity {
             normal_x: vec![0.0; rows * columns],
             normal_y: vec![0.0; rows * columns],
             phi: vec![0.0; (rows + 1) * (columns + 1)],
-            volume: vec![0.0; rows * columns],
+            volume: vec![1.0; rows * columns],
             cell: vec![0u8; rows * columns],
-            body: vec![0u8; rows * columns],
-            mask: vec![0u8; rows * columns],
+            body: vec![NO_BODY; rows * columns],
             rows,
             columns,
             x_offset,
             y_offset,
             cell_size,
+            periodic_x: false,
+            periodic_y: false,
         }
     }
 
@@ -56,11 +62,11 @@ impl FluidQuantity {
         &mut self.dst[row * self.columns + column]
     }
 
-    pub fn body_at(&self, row: usize, column: usize) -> u8 {
+    pub fn body_at(&self, row: usize, column: usize) -> u32 {
         self.body[row * self.columns + column]
     }
 
-    pub fn body_at_mut(&mut self, row: usize, column: usize) -> &mut u8 {
+    pub fn body_at_mut(&mut self, row: usize, column: usize) -> &mut u32 {
         &mut self.body[row * self.columns + column]
     }
 
@@ -72,14 +78,6 @@ impl FluidQuantity {
         &mut self.cell[row * self.columns + column]
     }
 
-    pub fn mask_at(&self, row: usize, column: usize) -> u8 {
-        self.mask[row * self.columns + column]
-    }
-
-    pub fn mask_at_mut(&mut self, row: usize, column: usize) -> &mut u8 {
-        &mut self.mask[row * self.columns + column]
-    }
-
     pub fn normal_x_at(&self, row: usize, column: usize) -> f64 {
         self.normal_x[row * self.columns + column]
     }
@@ -104,6 +102,54 @@ impl FluidQuantity {
         &mut self.volume[row * self.columns + column]
     }
 
+    // Number of distinct columns before the grid repeats. Face centred grids store the wrapped face twice
+    pub fn period_x(&self) -> usize {
+        if self.x_offset == 0.0 { self.columns - 1 } else { self.columns }
+    }
+
+    // Number of distinct rows before the grid repeats. Face centred grids store the wrapped face twice
+    pub fn period_y(&self) -> usize {
+        if self.y_offset == 0.0 { self.rows - 1 } else { self.rows }
+    }
+
+    // Maps a column index onto the grid, wrapping in periodic directions and clamping otherwise
+    pub fn wrap_column(&self, column: isize) -> usize {
+        if self.periodic_x {
+            column.rem_euclid(self.period_x() as isize) as usize
+        } else {
+            clamp(column, 0, self.columns as isize - 1) as usize
+        }
+    }
+
+    // Maps a row index onto the grid, wrapping in periodic directions and clamping otherwise
+    pub fn wrap_row(&self, row: isize) -> usize {
+        if self.periodic_y {
+            row.rem_euclid(self.period_y() as isize) as usize
+        } else {
+            clamp(row, 0, self.rows as isize - 1) as usize
+        }
+    }
+
+    // Returns the value at a possibly out of range index, see wrap_row and wrap_column
+    pub fn wrapped_at(&self, row: isize, column: isize) -> f64 {
+        self.at(self.wrap_row(row), self.wrap_column(column))
+    }
+
+    // Copies the first stored face onto its duplicate at the far end of periodic directions
+    pub fn copy_periodic_faces(&mut self) {
+        if self.periodic_x && self.period_x() < self.columns {
+            for row in 0..self.rows {
+                *self.at_mut(row, self.columns - 1) = self.at(row, 0);
+            }
+        }
+
+        if self.periodic_y && self.period_y() < self.rows {
+            for column in 0..self.columns {
+                *self.at_mut(self.rows - 1, column) = self.at(0, column);
+            }
+        }
+    }
+
     pub fn swap_buffers(&mut self) {
         swap(&mut self.src, &mut self.dst);
     }
@@ -130,17 +176,22 @@ impl FluidQuantity {
         }
     }
 
-    pub fn fill_solid_fields(&mut self, bodies: &Vec<SolidBody>) {
+    // Samples the distance to the nearest body at every corner and centre of the grid, looking bodies up through a
+    // bin grid so that only those near a point are evaluated. Points further than a couple of cells from every body
+    // only need to know they are outside, so they take that distance with no body and no normal
+    pub fn fill_solid_fields(&mut self, bodies: &[SolidBody]) {
         if !bodies.is_empty() {
+            let band = 2.0 * self.cell_size;
+            let grid = BodyGrid::new(bodies, (self.columns + 1) as f64 * self.cell_size, (self.rows + 1) as f64 * self.cell_size, 8.0 * self.cell_size, band);
+
+            let nearest = |x: f64, y: f64| grid.nearest(bodies, x, y);
+
             for row in 0..(self.rows + 1) {
                 for column in 0..(self.columns + 1) {
                     let x = (column as f64 + self.x_offset - 0.5) * self.cell_size;
                     let y = (row as f64 + self.y_offset - 0.5) * self.cell_size;
 
-                    self.phi[column + row * (self.columns + 1)] = bodies[0].distance(x, y);
-                    for index in 1..bodies.len() {
-                        self.phi[column + row * (self.columns + 1)] = min(bodies[0].distance(x, y), bodies[index].distance(x, y));
-                    }
+                    self.phi[column + row * (self.columns + 1)] = nearest(x, y).map_or(band, |(distance, _)| min(distance, band));
                 }
             }
 
@@ -149,18 +200,6 @@ impl FluidQuantity {
                     let x = (column as f64 + self.x_offset) * self.cell_size;
                     let y = (row as f64 + self.y_offset) * self.cell_size;
 
-                    *self.body_at_mut(row, column) = 0;
-                    let mut d = bodies[0].distance(x, y);
-                    for index in 1..bodies.len() {
-                        let id = bodies[index].distance(x, y);
-
-                        if id < d {
-                            // Note here that if there are more than 256 objects there will be errors
-                            *self.body_at_mut(row, column) = index as u8;
-                            d = id;
-                        }
-                    }
-
                     let idxp = column + row * (self.columns + 1);
                     self.volume[column + row * self.columns] = 1.0 - occupancy(self.phi[idxp], self.phi[idxp + 1], self.phi[idxp + self.columns + 1], self.phi[idxp + self.columns + 2]);
 
@@ -168,10 +207,14 @@ impl FluidQuantity {
                         self.volume[column + row * self.columns] = 0.0;
                     }
 
-                    // This needs to be refactored
-                    let output = bodies[self.body_at(row, column) as usize].distance_normal(x, y);
-                    *self.normal_x_at_mut(row, column) = output.0;
-                    *self.normal_y_at_mut(row, column) = output.1;
+                    let (body, normal) = match nearest(x, y) {
+                        Some((_, index)) => (index, bodies[index as usize].distance_normal(x, y)),
+                        None => (NO_BODY, (0.0, 0.0))
+                    };
+
+                    *self.body_at_mut(row, column) = body;
+                    *self.normal_x_at_mut(row, column) = normal.0;
+                    *self.normal_y_at_mut(row, column) = normal.1;
 
                     if self.volume[column + row * self.columns] == 0.0 {
                         *self.cell_at_mut(row, column) = 1;
@@ -183,75 +226,44 @@ impl FluidQuantity {
         }
     }
 
-    pub fn fill_solid_mask(&mut self) {
-        for row in 0..(self.rows - 1) {
-            for column in 0..(self.columns - 1) {
-                if self.cell_at(row, column) == 1 {
-                    let nx = self.normal_x_at(row, column);
-                    let ny = self.normal_y_at(row, column);
+    // Copies solid geometry from a quantity sampled at the same locations, avoiding a second distance evaluation
+    pub fn copy_solid_fields(&mut self, other: &FluidQuantity) {
+        self.phi.copy_from_slice(&other.phi);
+        self.volume.copy_from_slice(&other.volume);
+        self.cell.copy_from_slice(&other.cell);
+        self.body.copy_from_slice(&other.body);
+        self.normal_x.copy_from_slice(&other.normal_x);
+        self.normal_y.copy_from_slice(&other.normal_y);
+    }
 
-                    *self.mask_at_mut(row, column) = 0;
-                    if nx != 0.0 && self.cell_at(row, (column as isize + nx.signum() as isize) as usize) != 0 {
-                        *self.mask_at_mut(row, column) |= 1;
-                    }
+    // Distance of each sample point into the solid, averaged from the distance field at the corners around it
+    pub fn solid_depth(&self) -> Vec<f64> {
+        let mut depth = vec![0.0; self.rows * self.columns];
 
-                    if ny != 0.0 && self.cell_at((row as isize + ny.signum() as isize) as usize, column) != 0 {
-                        *self.mask_at_mut(row, column) |= 2;
-                    }
-                }
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let idxp = column + row * (self.columns + 1);
+                depth[column + row * self.columns] = -0.25 * (self.phi[idxp] + self.phi[idxp + 1] + self.phi[idxp + self.columns + 1] + self.phi[idxp + self.columns + 2]);
             }
         }
-    }
-
-    pub fn extrapolate_normal(&mut self, idx: usize) -> f64 {
-        let nx = self.normal_x[idx];
-        let ny = self.normal_y[idx];
-
-        let src_x = self.src[(idx as isize + nx.signum() as isize) as usize];
-        let src_y = self.src[(idx as isize + (ny.signum() as isize * self.columns as isize)) as usize];
 
-        (nx.abs() * src_x + ny.abs() * src_y) / (nx.abs() + ny.abs())
+        depth
     }
 
-    pub fn free_neighbour(&mut self, idx: usize, border: &mut Vec<usize>, mask: u8) {
-        self.mask[idx] &= !mask;
-        if self.cell[idx] != 0 && self.mask[idx] == 0 {
-            border.push(idx);
+    // Extends the fluid values into solid cells up to the given number of cells from the surface, following the
+    // distance field so that values are carried along the surface normal. Usually called after the projection so that
+    // interpolation near bodies reads sensible values
+    pub fn extrapolate(&mut self, layers: usize) {
+        if self.cell.iter().all(|&cell| cell == 0) {
+            return;
         }
-    }
-
-    pub fn extrapolate(&mut self) {
-        self.fill_solid_mask();
-
-        let mut border = vec![0 as usize; 0];
-
-        for row in 0..(self.rows - 1) {
-            for column in 0..(self.columns - 1) {
-                let idx = column + row * self.columns;
 
-                if self.cell[idx] != 0 && self.mask[idx] == 0 {
-                    border.push(idx);
-                }
-            }
-        }
+        let known: Vec<bool> = self.cell.iter().map(|&cell| cell == 0).collect();
+        let depth = self.solid_depth();
 
-        while !border.is_empty() {
-            let idx = border.pop().unwrap();
+        let period_x = self.periodic_x.then(|| self.period_x());
+        let period_y = self.periodic_y.then(|| self.period_y());
 
-            self.src[idx] = self.extrapolate_normal(idx);
-
-            if self.normal_x[idx - 1] > 0.0 {
-                self.free_neighbour(idx - 1, &mut border, 1);
-            }
-            if self.normal_x[idx + 1] < 0.0 {
-                self.free_neighbour(idx + 1, &mut border, 1);
-            }
-            if self.normal_y[idx - self.columns] > 0.0 {
-                self.free_neighbour(idx - self.columns, &mut border, 2);
-            }
-            if self.normal_y[idx + self.columns] < 0.0 {
-                self.free_neighbour(idx + self.columns, &mut border, 2);
-            }
-        }
+        extrapolate(&mut self.src, &known, &depth, self.rows, self.columns, period_x, period_y, layers as f64 * self.cell_size);
     }
 }
\ No newline at end of file
@@ -1,7 +1,7 @@
 use crate::util::sparse::Sparse;
 use crate::util::helper::max;
 
-pub fn dot_product(a: &Vec<f64>, b: &Vec<f64>) -> f64 {
+pub fn dot_product(a: &[f64], b: &[f64]) -> f64 {
     let mut result = 0.0;
     for element in 0..a.len() {
         result += a[element] * b[element];
@@ -10,47 +10,29 @@ pub fn dot_product(a: &Vec<f64>, b: &Vec<f64>) -> f64 {
 }
 
 // Multiplies pressure matrix with vector b and stores in dst
-pub fn matrix_vector_product(dst: &mut Vec<f64>, b: &Vec<f64>, a: &Sparse, rows: usize, columns: usize) {
+pub fn matrix_vector_product(dst: &mut [f64], b: &[f64], a: &Sparse, rows: usize, columns: usize) {
     for row in 0..rows {
         for column in 0..columns {
             let element = row * columns + column;
-            let mut t = a.diagonals[element] * b[element];
-
-            if column > 0 {
-                t += a.plus_x[element - 1] * b[element - 1];
-            }
-
-            if row > 0 {
-                t += a.plus_y[element - columns] * b[element - columns];
-            }
-
-            if column < columns - 1 {
-                t += a.plus_x[element] * b[element + 1];
-            }
-
-            if row < rows - 1 {
-                t += a.plus_y[element] * b[element + columns];
-            }
-
-            dst[element] = t;
+            dst[element] = a.diagonals[element] * b[element] + a.off_diagonal_product(b, row, column, rows, columns);
         }
     }
 }
 
-pub fn scaled_add1(dst: &mut Vec<f64>, b: &Vec<f64>, s: f64) {
+pub fn scaled_add1(dst: &mut [f64], b: &[f64], s: f64) {
     for element in 0..dst.len() {
         dst[element] += b[element] * s;
     }
 }
 
-pub fn scaled_add2(dst: &mut Vec<f64>, a: &Vec<f64>,  s: f64) {
+pub fn scaled_add2(dst: &mut [f64], a: &[f64], s: f64) {
     for element in 0..dst.len() {
         dst[element] *= s;
         dst[element] += a[element];
     }
 }
 
-pub fn infinity_norm(a: &Vec<f64>) -> f64 {
+pub fn infinity_norm(a: &[f64]) -> f64 {
     let mut max_a = 0.0;
     for element in a {
         max_a = max(max_a, element.abs());
@@ -1,5 +1,7 @@
+pub mod extrapolation;
 pub mod fluid_quantity;
 pub mod helper;
 pub mod linear_algebra;
 pub mod sparse;
-pub mod occupancy;
\ No newline at end of file
+pub mod occupancy;
+pub mod scalar_field;
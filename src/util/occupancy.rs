@@ -15,7 +15,7 @@ pub fn occupancy(d11: f64, d12: f64, d21: f64, d22: f64) -> f64 {
         b = (b << 1) | if ds[i] < 0.0 {1} else {0};
     }
 
-    return match b {
+    match b {
         0x0 => 0.0,
         0x1 => triangle_occupancy(d21, d11, d12),
         0x2 => triangle_occupancy(d11, d12, d22),
@@ -0,0 +1,19 @@
+use crate::util::fluid_quantity::FluidQuantity;
+
+// Named passive scalar carried by the flow, such as a dye channel or tracer concentration
+pub struct ScalarField {
+    pub name:     String,
+    pub quantity: FluidQuantity,
+    pub diffusivity: f64,
+}
+
+impl ScalarField {
+    // Scalars are stored at cell centres, matching the density field
+    pub fn new(name: &str, rows: usize, columns: usize, cell_size: f64) -> ScalarField {
+        ScalarField {
+            name:     name.to_string(),
+            quantity: FluidQuantity::new(rows, columns, 0.5, 0.5, cell_size),
+            diffusivity: 0.0,
+        }
+    }
+}
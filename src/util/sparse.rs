@@ -1,7 +1,11 @@
+// Five point symmetric matrix over a grid. In periodic directions the last plus_x or plus_y
+// entry of each row or column couples back to the first
 pub struct Sparse {
     pub diagonals: Vec<f64>,
     pub plus_x:    Vec<f64>,
     pub plus_y:    Vec<f64>,
+    pub periodic_x: bool,
+    pub periodic_y: bool,
 }
 
 impl Sparse {
@@ -9,7 +13,120 @@ impl Sparse {
         Sparse {
             diagonals: vec![0.0; size],
             plus_x:    vec![0.0; size],
-            plus_y:    vec![0.0; size]
+            plus_y:    vec![0.0; size],
+            periodic_x: false,
+            periodic_y: false
         }
     }
-}
\ No newline at end of file
+
+    // Returns the sum of the off-diagonal entries of a row multiplied by the matching entries of b
+    pub fn off_diagonal_product(&self, b: &[f64], row: usize, column: usize, rows: usize, columns: usize) -> f64 {
+        let element = row * columns + column;
+        let mut t = 0.0;
+
+        if column > 0 {
+            t += self.plus_x[element - 1] * b[element - 1];
+        } else if self.periodic_x {
+            t += self.plus_x[element + columns - 1] * b[element + columns - 1];
+        }
+
+        if row > 0 {
+            t += self.plus_y[element - columns] * b[element - columns];
+        } else if self.periodic_y {
+            t += self.plus_y[element + (rows - 1) * columns] * b[element + (rows - 1) * columns];
+        }
+
+        if column < columns - 1 {
+            t += self.plus_x[element] * b[element + 1];
+        } else if self.periodic_x {
+            t += self.plus_x[element] * b[element + 1 - columns];
+        }
+
+        if row < rows - 1 {
+            t += self.plus_y[element] * b[element + columns];
+        } else if self.periodic_y {
+            t += self.plus_y[element] * b[element - (rows - 1) * columns];
+        }
+
+        t
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Matrix with a different coupling on every face, so that a wrong index shows up in the product
+    fn matrix(rows: usize, columns: usize, periodic_x: bool, periodic_y: bool) -> Sparse {
+        let mut a = Sparse::new(rows * columns);
+        a.periodic_x = periodic_x;
+        a.periodic_y = periodic_y;
+
+        for element in 0..rows * columns {
+            a.diagonals[element] = 10.0;
+            a.plus_x[element] = -1.0 - 0.1 * element as f64;
+            a.plus_y[element] = -2.0 - 0.01 * element as f64;
+        }
+
+        a
+    }
+
+    // Off-diagonal product from the matrix written out in full, coupling each cell to the one after it on each axis
+    fn dense_product(a: &Sparse, b: &[f64], rows: usize, columns: usize) -> Vec<f64> {
+        let mut product = vec![0.0; rows * columns];
+
+        for row in 0..rows {
+            for column in 0..columns {
+                let element = row * columns + column;
+
+                if column < columns - 1 || a.periodic_x {
+                    let right = row * columns + (column + 1) % columns;
+                    product[element] += a.plus_x[element] * b[right];
+                    product[right] += a.plus_x[element] * b[element];
+                }
+
+                if row < rows - 1 || a.periodic_y {
+                    let below = ((row + 1) % rows) * columns + column;
+                    product[element] += a.plus_y[element] * b[below];
+                    product[below] += a.plus_y[element] * b[element];
+                }
+            }
+        }
+
+        product
+    }
+
+    fn check(periodic_x: bool, periodic_y: bool) {
+        let (rows, columns) = (3, 4);
+        let a = matrix(rows, columns, periodic_x, periodic_y);
+        let b: Vec<f64> = (0..rows * columns).map(|element| (0.7 * element as f64).sin()).collect();
+        let expected = dense_product(&a, &b, rows, columns);
+
+        for row in 0..rows {
+            for column in 0..columns {
+                let product = a.off_diagonal_product(&b, row, column, rows, columns);
+                assert!((product - expected[row * columns + column]).abs() < 1e-12, "row {} column {}: {} != {}", row, column, product, expected[row * columns + column]);
+            }
+        }
+    }
+
+    #[test]
+    fn closed_edges_do_not_wrap() {
+        check(false, false);
+    }
+
+    #[test]
+    fn periodic_edges_wrap_in_x() {
+        check(true, false);
+    }
+
+    #[test]
+    fn periodic_edges_wrap_in_y() {
+        check(false, true);
+    }
+
+    #[test]
+    fn periodic_edges_wrap_in_both() {
+        check(true, true);
+    }
+}